@@ -0,0 +1,345 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use gh_client::crawl::{CancellationToken, CrawlFailure, CrawlProgress};
+use gh_client::diff::diff_snapshots;
+use gh_client::export::{export_tables, ExportFormat};
+use gh_client::snapshot::GHSnapshot;
+use gh_client::storage::{sync_org, Storage};
+use gh_client::sync::sync_org_incremental;
+use gh_client::stats::{language_totals, users_by_language, LanguageTotal, LanguageUser};
+use gh_client::{GHClient, GHOrganization, GHRepository, GHUser};
+use surf::Client;
+use tracing_subscriber::EnvFilter;
+
+mod output;
+
+use output::{print, OutputFormat, Tabular};
+
+/// Query programming language skills of GitHub organization members.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// GH-API token(s), comma separated. Requests rotate between multiple tokens.
+    /// Falls back to `~/.config/gh-cli/token` (one token per line).
+    #[arg(long, env = "GH_API_TOKEN", value_delimiter = ',', hide_env_values = true, global = true)]
+    token: Vec<String>,
+
+    #[arg(long, value_enum, default_value = "table", global = true)]
+    format: OutputFormat,
+
+    /// SQLite database filled by `sync`. If given, `languages` and `who-knows` query it instead
+    /// of crawling the organization.
+    #[arg(long, env = "GH_CLI_DB", global = true)]
+    db: Option<PathBuf>,
+
+    /// Only estimate the GH-API requests of crawling the organization of the command and whether
+    /// they fit into the remaining rate-limit.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Count the repository pages of every member for --dry-run instead of extrapolating them
+    /// from a sample, which costs about half of the crawl.
+    #[arg(long, global = true, requires = "dry_run")]
+    exact: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the organizations of the token's user.
+    Orgs,
+    /// List the members of an organization.
+    Members { org: String },
+    /// List the repositories of a user.
+    Repos { user: String },
+    /// Count repositories and users per language within an organization.
+    Languages { org: String },
+    /// List the members of an organization with repositories in a language.
+    WhoKnows {
+        language: String,
+        #[arg(long)]
+        org: String,
+    },
+    /// Export users, repositories and user-languages tables of an organization to files.
+    Export {
+        org: String,
+        /// Directory to write the tables to.
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+        /// One of csv, jsonl or parquet.
+        #[arg(long = "as", default_value = "csv")]
+        export_format: ExportFormat,
+    },
+    /// Write a versioned snapshot of the members and repositories of an organization.
+    Snapshot {
+        org: String,
+        /// File to write the snapshot to, stdout if omitted.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Compare two snapshot files, reporting joined and left members, new and removed
+    /// repositories and changed language counts.
+    Diff { old: PathBuf, new: PathBuf },
+    /// Fetch the members and repositories of an organization into the `--db` database.
+    ///
+    /// Only members that pushed since the last sync get their repositories fetched again.
+    Sync {
+        org: String,
+        /// Fetch the repositories of all members instead of only the changed ones.
+        #[arg(long)]
+        full: bool,
+        /// Fetch the repositories of members completely if their last complete fetch is older.
+        #[arg(long, default_value = "7")]
+        max_age_days: i64,
+    },
+}
+
+impl Command {
+    /// Organization the command crawls, given whether a database is queried instead.
+    fn crawled_org(&self, db: bool) -> Option<&str> {
+        match self {
+            Command::Languages { org } | Command::WhoKnows { org, .. } if !db => Some(org),
+            Command::Export { org, .. } | Command::Snapshot { org, .. } | Command::Sync { org, .. } => Some(org),
+            _ => None,
+        }
+    }
+
+    /// Organization whose member list the command requests, given whether a database is queried
+    /// instead.
+    fn member_org(&self, db: bool) -> Option<&str> {
+        match self {
+            Command::Members { org } => Some(org),
+            _ => self.crawled_org(db),
+        }
+    }
+}
+
+impl Tabular for GHUser {
+    fn headers() -> Vec<&'static str> { vec!["login", "id"] }
+    fn row(&self) -> Vec<String> { vec![self.login.clone(), self.id.to_string()] }
+}
+
+impl Tabular for GHOrganization {
+    fn headers() -> Vec<&'static str> { vec!["login", "description"] }
+    fn row(&self) -> Vec<String> { vec![self.login.clone(), self.description.clone().unwrap_or_default()] }
+}
+
+impl Tabular for GHRepository {
+    fn headers() -> Vec<&'static str> { vec!["name", "language"] }
+    fn row(&self) -> Vec<String> { vec![self.name.clone(), self.language.clone().unwrap_or_default()] }
+}
+
+impl Tabular for LanguageTotal {
+    fn headers() -> Vec<&'static str> { vec!["language", "repositories", "users"] }
+    fn row(&self) -> Vec<String> { vec![self.language.clone(), self.repositories.to_string(), self.users.to_string()] }
+}
+
+impl Tabular for LanguageUser {
+    fn headers() -> Vec<&'static str> { vec!["login", "repositories"] }
+    fn row(&self) -> Vec<String> { vec![self.login.clone(), self.repositories.to_string()] }
+}
+
+/// Tokens from the command line / environment, or else from the config file.
+fn load_tokens(tokens: Vec<String>) -> Vec<String> {
+    let tokens: Vec<String> = tokens.into_iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+    if !tokens.is_empty() {
+        return tokens;
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("gh-cli").join("token"));
+    match config.map(std::fs::read_to_string) {
+        Some(Ok(content)) => content.lines().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect(),
+        _ => {
+            log::warn!("No GH-API token configured, unauthenticated requests are limited to 60 per hour.");
+            Vec::new()
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME/gh-cli/{org}.crawl.json`, where an interrupted crawl of `org` is kept.
+fn progress_path(org: &str) -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|cache| cache.join("gh-cli").join(format!("{org}.crawl.json")))
+}
+
+fn save_progress(path: &Path, progress: &CrawlProgress) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(std::fs::write(path, progress.to_json()?)?)
+}
+
+/// Crawl the member repositories of `org`, resuming a previously interrupted crawl.
+///
+/// The first Ctrl-C stops the crawl and keeps its progress for the next crawl of `org`, a second
+/// one exits right away. Members that failed are retried by the next crawl, unless they failed
+/// for reasons retrying does not resolve, like deleted users.
+async fn crawl(client: &GHClient, org: &str) -> Result<Vec<(GHUser, Vec<GHRepository>)>> {
+    let cancel = CancellationToken::default();
+    {
+        let cancel = cancel.clone();
+        ctrlc::set_handler(move || {
+            if cancel.is_cancelled() {
+                std::process::exit(130);
+            }
+            eprintln!("Stopping the crawl, press Ctrl-C again to exit right away.");
+            cancel.cancel();
+        })?;
+    }
+
+    let path = progress_path(org);
+    let progress = match path.as_ref().filter(|path| path.exists()) {
+        Some(path) => {
+            let progress = CrawlProgress::from_json(&std::fs::read_to_string(path)?)?;
+            log::info!("resuming crawl of {org} with {} of {} members done", progress.done.len(), progress.total());
+            progress
+        }
+        None => CrawlProgress::new(org),
+    };
+    let progress = client
+        .crawl_org(progress, &cancel, |progress| {
+            // keep the progress of crawls that are killed instead of interrupted
+            if let (Some(path), 0) = (&path, progress.done.len() % 10) {
+                if let Err(msg) = save_progress(path, progress) {
+                    log::warn!("Failed to save crawl progress to {}: {msg}", path.display());
+                }
+            }
+        })
+        .await?;
+
+    // members that failed for good are reported below instead of being retried on every run
+    match path {
+        Some(path) if progress.is_settled() && path.exists() => std::fs::remove_file(path)?,
+        Some(_) if progress.is_settled() => {}
+        Some(path) => {
+            save_progress(&path, &progress)?;
+            if cancel.is_cancelled() {
+                return Err(anyhow!(
+                    "Crawl of {org} stopped after {} of {} members, run again to resume it",
+                    progress.done.len(),
+                    progress.total()
+                ));
+            }
+        }
+        None if cancel.is_cancelled() => return Err(anyhow!("Crawl of {org} stopped")),
+        None => {}
+    }
+    warn_failures(org, &progress.failures);
+    Ok(progress.into_data())
+}
+
+/// Report members whose repositories are missing, or were kept from an earlier sync, on stderr.
+fn warn_failures(org: &str, failures: &[CrawlFailure]) {
+    for failure in failures {
+        let retry = if failure.retriable { "retriable" } else { "not retriable" };
+        eprintln!("Missing repositories of {}, {:?} and {retry}: {}", failure.login, failure.kind, failure.message);
+    }
+    if failures.iter().any(|failure| failure.retriable) {
+        eprintln!("{} members of {org} failed, run again to retry them", failures.len());
+    } else if !failures.is_empty() {
+        eprintln!("{} members of {org} failed", failures.len());
+    }
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    // warnings about tokens and failed members show without RUST_LOG, apart from the output
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).init();
+    let cli = Cli::parse();
+    let tokens = load_tokens(cli.token);
+    let authenticated = !tokens.is_empty();
+    let client = GHClient::with_tokens(Client::new(), tokens);
+
+    // without `read:org` or SSO authorization only the public members are listed, which
+    // check_org_access warns about
+    if let (true, Some(org)) = (authenticated, cli.command.member_org(cli.db.is_some())) {
+        if let Err(msg) = client.check_org_access(org).await {
+            log::warn!("Failed to check the access of the token to {org}: {msg}");
+        }
+    }
+
+    if cli.dry_run {
+        let org = cli.command.crawled_org(cli.db.is_some()).ok_or_else(|| anyhow!("--dry-run requires a command crawling an organization"))?;
+        let estimate = if cli.exact { client.estimate_org_crawl_exact(org).await? } else { client.estimate_org_crawl(org).await? };
+        match cli.format {
+            OutputFormat::Table => print!("{estimate}"),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&estimate)?),
+        }
+        return Ok(());
+    }
+
+    match cli.command {
+        Command::Orgs => print(&client.get_user_orgs().await?, cli.format)?,
+        Command::Members { org } => print(&client.get_org_members(&org).await?, cli.format)?,
+        Command::Repos { user } => print(&client.get_user_repositories(&user).await?, cli.format)?,
+        Command::Languages { org } => match &cli.db {
+            Some(db) => print(&Storage::open(db)?.language_totals(&org)?, cli.format)?,
+            None => {
+                let user_repos = crawl(&client, &org).await?;
+                print(&language_totals(&user_repos), cli.format)?
+            }
+        },
+        Command::WhoKnows { language, org } => match &cli.db {
+            Some(db) => print(&Storage::open(db)?.users_by_language(&org, &language)?, cli.format)?,
+            None => {
+                let user_repos = crawl(&client, &org).await?;
+                print(&users_by_language(&user_repos, &language), cli.format)?
+            }
+        },
+        Command::Export { org, dir, export_format } => {
+            let user_repos = crawl(&client, &org).await?;
+            for path in export_tables(&user_repos, &dir, export_format)? {
+                println!("{}", path.display());
+            }
+        }
+        Command::Snapshot { org, output } => {
+            let snapshot = GHSnapshot::new(&org, crawl(&client, &org).await?);
+            match output {
+                Some(path) => std::fs::write(path, snapshot.to_json()?)?,
+                None => println!("{}", snapshot.to_json()?),
+            }
+        }
+        Command::Diff { old, new } => {
+            let old = GHSnapshot::from_json(&std::fs::read_to_string(old)?)?;
+            let new = GHSnapshot::from_json(&std::fs::read_to_string(new)?)?;
+            let diff = diff_snapshots(&old, &new);
+            match cli.format {
+                OutputFormat::Table => print!("{diff}"),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+            }
+        }
+        Command::Sync { org, full, max_age_days } => {
+            let db = cli.db.ok_or_else(|| anyhow!("sync requires a --db to store the data in"))?;
+            let mut storage = Storage::open(db)?;
+            if full {
+                warn_failures(&org, &sync_org(&client, &mut storage, &org).await?);
+            } else {
+                let report = sync_org_incremental(&client, &mut storage, &org, chrono::Duration::days(max_age_days)).await?;
+                warn_failures(&org, &report.failed);
+                match cli.format {
+                    OutputFormat::Table => println!(
+                        "{} members, {} joined, {} left, {} refetched, {} unchanged, {} failed",
+                        report.members,
+                        report.joined.len(),
+                        report.left.len(),
+                        report.refetched.len(),
+                        report.unchanged,
+                        report.failed.len()
+                    ),
+                    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                }
+            }
+        }
+    }
+    Ok(())
+}
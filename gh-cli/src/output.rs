@@ -0,0 +1,61 @@
+//! Printing of command results as plain text tables or JSON.
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned plain text columns.
+    Table,
+    /// Pretty printed JSON.
+    Json,
+}
+
+/// A result that can be printed as table as well as JSON.
+pub trait Tabular: Serialize {
+    fn headers() -> Vec<&'static str>;
+    fn row(&self) -> Vec<String>;
+}
+
+/// Print `items` to stdout in the requested format.
+pub fn print<T: Tabular>(items: &[T], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => print!("{}", table(T::headers(), items.iter().map(T::row).collect())),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(items)?),
+    }
+    Ok(())
+}
+
+/// Render rows as left aligned columns separated by two spaces.
+fn table(headers: Vec<&str>, rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(cell, width)| format!("{cell:width$}")).collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut output = line(headers.iter().map(|header| header.to_uppercase()).collect());
+    for row in rows {
+        output.push_str(&line(row));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_alignment() {
+        let rows = vec![vec!["Rust".to_string(), "12".to_string()], vec!["TypeScript".to_string(), "3".to_string()]];
+        assert_eq!(
+            "LANGUAGE    REPOS\nRust        12\nTypeScript  3\n",
+            table(vec!["language", "repos"], rows)
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Crawls of the member repositories of an organization that can be cancelled and resumed.
+//!
+//! A crawl reports its [CrawlProgress] after every member. Callers persist it wherever they
+//! like (a file, local storage) and pass it to [GHClient::crawl_org] again to resume the crawl,
+//! fetching only the members still pending.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use surf::StatusCode;
+
+use crate::{GHClient, GHError, GHRepository, GHUser};
+
+/// Flag shared with a long running operation to stop it early.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Why fetching the repositories of a member failed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The user was deleted or renamed since the member list was fetched.
+    NotFound,
+    /// The token lacks access, e.g. because the user blocked it.
+    Forbidden,
+    RateLimited,
+    /// GitHub failed to answer, e.g. with a 502 for users with very many repositories.
+    Server,
+    Network,
+    /// The response could not be understood.
+    Invalid,
+}
+
+impl FailureKind {
+    /// Classify an error returned by a [GHClient] method.
+    pub fn of(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<GHError>() {
+            Some(GHError::Transport(_)) => FailureKind::Network,
+            Some(GHError::Status(StatusCode::NotFound | StatusCode::Gone)) => FailureKind::NotFound,
+            Some(GHError::RateLimited(_) | GHError::Status(StatusCode::TooManyRequests)) => FailureKind::RateLimited,
+            Some(GHError::Status(StatusCode::Unauthorized | StatusCode::Forbidden)) => FailureKind::Forbidden,
+            Some(GHError::Status(status)) if status.is_server_error() => FailureKind::Server,
+            Some(GHError::Status(_) | GHError::Body(_)) | None => FailureKind::Invalid,
+        }
+    }
+
+    /// Whether retrying the same request later may succeed.
+    pub fn is_retriable(self) -> bool {
+        matches!(self, FailureKind::RateLimited | FailureKind::Server | FailureKind::Network)
+    }
+}
+
+/// A member whose repositories could not be fetched.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CrawlFailure {
+    pub login: String,
+    pub kind: FailureKind,
+    pub retriable: bool,
+    pub message: String,
+}
+
+impl CrawlFailure {
+    pub fn new(login: &str, error: &anyhow::Error) -> Self {
+        let kind = FailureKind::of(error);
+        Self { login: login.to_string(), kind, retriable: kind.is_retriable(), message: error.to_string() }
+    }
+}
+
+impl fmt::Display for CrawlFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?})", self.login, self.kind)
+    }
+}
+
+/// One line naming the failed members and why they failed, for logs and sync records.
+pub fn failure_summary(failures: &[CrawlFailure]) -> String {
+    let members: Vec<String> = failures.iter().map(CrawlFailure::to_string).collect();
+    format!("Failed to fetch the repositories of {} members: {}", failures.len(), members.join(", "))
+}
+
+/// State and report of a crawl, serializable to resume it later.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CrawlProgress {
+    pub org: String,
+    /// Members of the organization, `None` until fetched.
+    pub members: Option<Vec<GHUser>>,
+    /// Members whose repositories were fetched, in the order they finished.
+    pub done: Vec<(GHUser, Vec<GHRepository>)>,
+    /// Members that failed during the last run of the crawl. They stay pending and are retried
+    /// when resuming.
+    #[serde(default)]
+    pub failures: Vec<CrawlFailure>,
+}
+
+impl CrawlProgress {
+    /// Progress of a crawl of `org` that did not start yet.
+    pub fn new(org: &str) -> Self {
+        Self { org: org.to_string(), members: None, done: Vec::new(), failures: Vec::new() }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Number of members, 0 until fetched.
+    pub fn total(&self) -> usize {
+        self.members.as_ref().map(Vec::len).unwrap_or(0)
+    }
+
+    /// Members whose repositories are not fetched yet.
+    pub fn pending(&self) -> Vec<GHUser> {
+        let done: HashSet<&str> = self.done.iter().map(|(user, _)| user.login.as_str()).collect();
+        self.members
+            .iter()
+            .flatten()
+            .filter(|user| !done.contains(user.login.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.members.is_some() && self.done.len() == self.total()
+    }
+
+    /// Whether resuming the crawl cannot fetch more members, because it is complete or all
+    /// pending members failed in the last run for reasons that retrying does not resolve.
+    pub fn is_settled(&self) -> bool {
+        self.members.is_some() && self.pending().len() == self.failures.len() && self.failures.iter().all(|failure| !failure.retriable)
+    }
+
+    /// The fetched `(user, repositories)` pairs in the order of the member list.
+    pub fn into_data(mut self) -> Vec<(GHUser, Vec<GHRepository>)> {
+        let order: HashMap<String, usize> = self
+            .members
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, user)| (user.login.clone(), index))
+            .collect();
+        self.done.sort_by_key(|(user, _)| order.get(&user.login).copied().unwrap_or(usize::MAX));
+        self.done
+    }
+
+    /// Like [CrawlProgress::into_data], with failed members keeping their repositories in
+    /// `previous` data of the organization. Failed members without previous data are left out.
+    pub fn into_data_with(mut self, previous: &[(GHUser, Vec<GHRepository>)]) -> Vec<(GHUser, Vec<GHRepository>)> {
+        let previous: HashMap<&str, &Vec<GHRepository>> = previous.iter().map(|(user, repos)| (user.login.as_str(), repos)).collect();
+        let failed: HashSet<&str> = self.failures.iter().map(|failure| failure.login.as_str()).collect();
+        let kept: Vec<(GHUser, Vec<GHRepository>)> = self
+            .members
+            .iter()
+            .flatten()
+            .filter(|user| failed.contains(user.login.as_str()))
+            .filter_map(|user| previous.get(user.login.as_str()).map(|repos| (user.clone(), (*repos).clone())))
+            .collect();
+        self.done.extend(kept);
+        self.into_data()
+    }
+}
+
+impl GHClient {
+    /// Fetch the repositories of all pending members of a crawl concurrently.
+    ///
+    /// Returns once every member is done or `cancel` is cancelled, calling `on_progress` after
+    /// fetching the member list and after every member. Members whose repositories cannot be
+    /// fetched are reported in [CrawlProgress::failures] and stay pending, so the returned
+    /// progress is only complete if all of them succeeded.
+    #[tracing::instrument(skip_all, fields(org = %progress.org))]
+    pub async fn crawl_org(
+        &self,
+        mut progress: CrawlProgress,
+        cancel: &CancellationToken,
+        mut on_progress: impl FnMut(&CrawlProgress),
+    ) -> Result<CrawlProgress> {
+        // the pending members are retried
+        progress.failures.clear();
+        if progress.members.is_none() {
+            progress.members = Some(self.get_org_members(&progress.org).await?);
+            on_progress(&progress);
+        }
+
+        let mut pending: FuturesUnordered<_> = progress
+            .pending()
+            .into_iter()
+            .map(|user| async move {
+                let repos = self.get_user_repositories(&user.login).await;
+                (user, repos)
+            })
+            .collect();
+        while !cancel.is_cancelled() {
+            let Some((user, repos)) = pending.next().await else { break };
+            match repos {
+                Ok(repos) => {
+                    progress.done.push((user, repos));
+                    on_progress(&progress);
+                }
+                Err(msg) => {
+                    tracing::warn!("Failed to fetch repos for {}: {msg}", user.login);
+                    progress.failures.push(CrawlFailure::new(&user.login, &msg));
+                    on_progress(&progress);
+                }
+            }
+        }
+        if cancel.is_cancelled() {
+            tracing::info!("crawl of {} cancelled after {} of {} members", progress.org, progress.done.len(), progress.total());
+        }
+        Ok(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{repo, user};
+    use rstest::*;
+
+    #[test]
+    fn test_progress() -> Result<()> {
+        let mut progress = CrawlProgress::new("octo");
+        assert!(!progress.is_complete());
+        progress.members = Some(vec![user("alice", 1), user("bob", 2), user("carol", 3)]);
+        progress.done.push((user("carol", 3), Vec::new()));
+        progress.done.push((user("alice", 1), Vec::new()));
+
+        let progress = CrawlProgress::from_json(&progress.to_json()?)?;
+        assert_eq!(vec![user("bob", 2)], progress.pending());
+        assert!(!progress.is_complete());
+        let logins: Vec<String> = progress.into_data().into_iter().map(|(user, _)| user.login).collect();
+        assert_eq!(vec!["alice", "carol"], logins);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_settled() {
+        let mut progress = CrawlProgress::new("octo");
+        assert!(!progress.is_settled());
+        progress.members = Some(vec![user("alice", 1), user("bob", 2)]);
+        progress.done.push((user("alice", 1), Vec::new()));
+        // bob is pending after a cancellation
+        assert!(!progress.is_settled());
+        progress.failures = vec![CrawlFailure::new("bob", &GHError::Status(StatusCode::BadGateway).into())];
+        assert!(!progress.is_settled());
+        progress.failures = vec![CrawlFailure::new("bob", &GHError::Status(StatusCode::NotFound).into())];
+        assert!(progress.is_settled());
+        progress.failures.clear();
+        progress.done.push((user("bob", 2), Vec::new()));
+        assert!(progress.is_settled());
+    }
+
+    #[test]
+    fn test_into_data_with() {
+        let mut progress = CrawlProgress::new("octo");
+        progress.members = Some(vec![user("alice", 1), user("bob", 2), user("carol", 3)]);
+        progress.done.push((user("carol", 3), vec![repo("c", Some("Rust"))]));
+        let not_found = anyhow::Error::from(GHError::Status(StatusCode::NotFound));
+        progress.failures = vec![CrawlFailure::new("alice", &not_found), CrawlFailure::new("bob", &not_found)];
+
+        let previous = vec![(user("alice", 1), vec![repo("a", Some("Go"))]), (user("carol", 3), Vec::new())];
+        let data = progress.clone().into_data_with(&previous);
+        assert_eq!(vec![(user("alice", 1), vec![repo("a", Some("Go"))]), (user("carol", 3), vec![repo("c", Some("Rust"))])], data);
+        assert_eq!(
+            "Failed to fetch the repositories of 2 members: alice (NotFound), bob (NotFound)",
+            failure_summary(&progress.failures)
+        );
+    }
+
+    #[rstest]
+    #[case(GHError::Status(StatusCode::NotFound).into(), FailureKind::NotFound, false)]
+    #[case(GHError::Status(StatusCode::BadGateway).into(), FailureKind::Server, true)]
+    #[case(GHError::Status(StatusCode::TooManyRequests).into(), FailureKind::RateLimited, true)]
+    #[case(GHError::RateLimited(StatusCode::Forbidden).into(), FailureKind::RateLimited, true)]
+    #[case(GHError::Status(StatusCode::Forbidden).into(), FailureKind::Forbidden, false)]
+    #[case(GHError::Transport("connection reset".into()).into(), FailureKind::Network, true)]
+    #[case(anyhow::anyhow!("Could not parse pagination header"), FailureKind::Invalid, false)]
+    fn test_failure_kind(#[case] error: anyhow::Error, #[case] kind: FailureKind, #[case] retriable: bool) {
+        let failure = CrawlFailure::new("alice", &error);
+        assert_eq!(kind, failure.kind);
+        assert_eq!(retriable, failure.retriable);
+        assert_eq!(error.to_string(), failure.message);
+    }
+}
@@ -0,0 +1,180 @@
+//! Changes between two snapshots of an organization, e.g. to see who picked up a language.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::GHSnapshot;
+use crate::{language_count, GHRepository, GHUser};
+
+/// A repository that was created or removed between two snapshots.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RepositoryChange {
+    pub login: String,
+    pub name: String,
+    pub language: Option<String>,
+}
+
+/// Change of the number of repositories of a member in a language.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct UserLanguageDelta {
+    pub login: String,
+    pub language: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+/// Change of the number of repositories in a language across the whole organization.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LanguageDelta {
+    pub language: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct SnapshotDiff {
+    /// Logins of members only present in the newer snapshot.
+    pub joined: Vec<String>,
+    /// Logins of members only present in the older snapshot.
+    pub left: Vec<String>,
+    /// Repositories of members present in both snapshots that were created.
+    pub repositories_added: Vec<RepositoryChange>,
+    /// Repositories of members present in both snapshots that were removed.
+    pub repositories_removed: Vec<RepositoryChange>,
+    /// Changed language counts of members present in both snapshots.
+    pub user_languages: Vec<UserLanguageDelta>,
+    /// Changed language counts of the organization, including joined and left members.
+    pub languages: Vec<LanguageDelta>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == SnapshotDiff::default()
+    }
+}
+
+/// Compare two snapshots, `old` being the earlier one.
+pub fn diff_snapshots(old: &GHSnapshot, new: &GHSnapshot) -> SnapshotDiff {
+    diff(&old.data, &new.data)
+}
+
+/// Compare the `(user, repositories)` pairs of an earlier and a later crawl.
+pub fn diff(old: &[(GHUser, Vec<GHRepository>)], new: &[(GHUser, Vec<GHRepository>)]) -> SnapshotDiff {
+    let old: BTreeMap<&str, &[GHRepository]> = old.iter().map(|(user, repos)| (user.login.as_str(), repos.as_slice())).collect();
+    let new: BTreeMap<&str, &[GHRepository]> = new.iter().map(|(user, repos)| (user.login.as_str(), repos.as_slice())).collect();
+
+    let mut result = SnapshotDiff {
+        joined: new.keys().filter(|login| !old.contains_key(*login)).map(|login| login.to_string()).collect(),
+        left: old.keys().filter(|login| !new.contains_key(*login)).map(|login| login.to_string()).collect(),
+        ..Default::default()
+    };
+
+    for (login, new_repos) in &new {
+        let Some(old_repos) = old.get(login) else { continue };
+        let change = |repo: &GHRepository| RepositoryChange { login: login.to_string(), name: repo.name.clone(), language: repo.language.clone() };
+        let old_names: BTreeSet<&str> = old_repos.iter().map(|repo| repo.name.as_str()).collect();
+        let new_names: BTreeSet<&str> = new_repos.iter().map(|repo| repo.name.as_str()).collect();
+        result.repositories_added.extend(new_repos.iter().filter(|repo| !old_names.contains(repo.name.as_str())).map(change));
+        result.repositories_removed.extend(old_repos.iter().filter(|repo| !new_names.contains(repo.name.as_str())).map(change));
+
+        for (language, before, after) in count_deltas(language_count(old_repos), language_count(new_repos)) {
+            result.user_languages.push(UserLanguageDelta { login: login.to_string(), language, before, after });
+        }
+    }
+
+    let totals = |data: &BTreeMap<&str, &[GHRepository]>| {
+        let repos: Vec<GHRepository> = data.values().flat_map(|repos| repos.iter().cloned()).collect();
+        language_count(&repos)
+    };
+    result.languages = count_deltas(totals(&old), totals(&new))
+        .into_iter()
+        .map(|(language, before, after)| LanguageDelta { language, before, after })
+        .collect();
+    result
+}
+
+/// `(language, before, after)` for every language whose count changed, sorted by language.
+fn count_deltas(
+    before: impl IntoIterator<Item = (String, usize)>,
+    after: impl IntoIterator<Item = (String, usize)>,
+) -> Vec<(String, usize, usize)> {
+    let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (language, count) in before {
+        counts.entry(language).or_default().0 = count;
+    }
+    for (language, count) in after {
+        counts.entry(language).or_default().1 = count;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(language, (before, after))| (language, before, after))
+        .collect()
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+        for login in &self.joined {
+            writeln!(f, "+ member {login}")?;
+        }
+        for login in &self.left {
+            writeln!(f, "- member {login}")?;
+        }
+        for repo in &self.repositories_added {
+            writeln!(f, "+ repository {}/{} ({})", repo.login, repo.name, repo.language.as_deref().unwrap_or("-"))?;
+        }
+        for repo in &self.repositories_removed {
+            writeln!(f, "- repository {}/{} ({})", repo.login, repo.name, repo.language.as_deref().unwrap_or("-"))?;
+        }
+        for delta in &self.user_languages {
+            writeln!(f, "~ {} {}: {} -> {}", delta.login, delta.language, delta.before, delta.after)?;
+        }
+        for delta in &self.languages {
+            writeln!(f, "~ language {}: {} -> {}", delta.language, delta.before, delta.after)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{repo, user};
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            (user("alice", 1), vec![repo("a", Some("Python"))]),
+            (user("bob", 2), vec![repo("b", Some("Java"))]),
+        ];
+        let new = vec![
+            (user("alice", 1), vec![repo("a", Some("Python")), repo("c", Some("Rust"))]),
+            (user("carol", 3), vec![repo("d", Some("Rust"))]),
+        ];
+        let result = diff(&old, &new);
+        assert_eq!(vec!["carol"], result.joined);
+        assert_eq!(vec!["bob"], result.left);
+        assert_eq!(vec![RepositoryChange { login: "alice".into(), name: "c".into(), language: Some("Rust".into()) }], result.repositories_added);
+        assert!(result.repositories_removed.is_empty());
+        assert_eq!(vec![UserLanguageDelta { login: "alice".into(), language: "Rust".into(), before: 0, after: 1 }], result.user_languages);
+        assert_eq!(
+            vec![
+                LanguageDelta { language: "Java".into(), before: 1, after: 0 },
+                LanguageDelta { language: "Rust".into(), before: 0, after: 2 },
+            ],
+            result.languages
+        );
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let data = vec![(user("alice", 1), vec![repo("a", Some("Python"))])];
+        let result = diff(&data, &data);
+        assert!(result.is_empty());
+        assert_eq!("No changes.\n", result.to_string());
+    }
+}
@@ -0,0 +1,254 @@
+//! Cost of crawling an organization, estimated from member counts and first-page `Link` headers.
+//!
+//! By default only a sample of the members has its repository pages counted and the rest is
+//! extrapolated, so that the estimate costs a few requests regardless of the organization size.
+//! The exact estimate counts the pages of every member, costing about half of a crawl.
+use std::fmt;
+
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use surf::http::Method;
+use surf::StatusCode;
+
+use crate::{GHClient, GHError, GHRateLimit, GHRepository, GHUser};
+
+/// Requests needed for [GHClient::get_org_member_repositories] of an organization.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GHCrawlEstimate {
+    pub org: String,
+    pub members: usize,
+    pub member_pages: usize,
+    /// Repository pages summed over all members.
+    pub repository_pages: usize,
+    /// Members whose repository pages were counted, those of the others are extrapolated.
+    pub sampled_members: usize,
+    /// Requests of a full crawl.
+    pub requests: usize,
+    /// Requests spent on the estimate itself.
+    pub estimate_requests: usize,
+    /// Budget left after the estimate, `None` if unknown.
+    pub rate_limit: Option<GHRateLimit>,
+}
+
+impl GHCrawlEstimate {
+    /// Whether a full crawl fits into the current rate-limit window.
+    pub fn fits(&self) -> Option<bool> {
+        self.rate_limit.as_ref().map(|limit| self.requests <= limit.remaining)
+    }
+}
+
+/// Members per page of [GHClient::get_org_members].
+const MEMBERS_PER_PAGE: usize = 30;
+/// Repositories per page of [GHClient::get_user_repositories].
+const REPOSITORIES_PER_PAGE: usize = 30;
+/// Members whose repository pages are counted by a sampled estimate.
+const SAMPLED_MEMBERS: usize = 10;
+
+/// Pages of a member list of `members` members, at least one even if empty.
+fn member_pages(members: usize) -> usize {
+    members.div_ceil(MEMBERS_PER_PAGE).max(1)
+}
+
+/// A `HEAD` request plus every page of the members, and the same for the repositories of every
+/// member.
+fn crawl_requests(member_pages: usize, members: usize, repository_pages: usize) -> usize {
+    1 + member_pages + members + repository_pages
+}
+
+/// Repository pages of `members` members, extrapolated from the `sampled` pages of some of them.
+fn extrapolate_pages(members: usize, sampled: &[usize]) -> usize {
+    if sampled.is_empty() {
+        return members;
+    }
+    let pages: usize = sampled.iter().sum();
+    (pages * members).div_ceil(sampled.len()).max(members)
+}
+
+/// Requests of crawling an organization again whose previous crawl returned `data`, assuming
+/// its members and their repositories did not change much since.
+pub fn recrawl_requests(data: &[(GHUser, Vec<GHRepository>)]) -> usize {
+    let repository_pages = data.iter().map(|(_, repos)| repos.len().div_ceil(REPOSITORIES_PER_PAGE).max(1)).sum();
+    crawl_requests(member_pages(data.len()), data.len(), repository_pages)
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimitResources {
+    resources: RateLimitCore,
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimitCore {
+    core: RateLimitWindow,
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimitWindow {
+    remaining: usize,
+    reset: i64,
+}
+
+impl GHClient {
+    /// Rate-limit of the core API as reported by `/rate_limit`, which does not count against it.
+    ///
+    /// Unlike [GHClient::rate_limit] this also works without tokens, but only reports the budget
+    /// of a single token of the pool.
+    pub async fn get_rate_limit(&self) -> Result<GHRateLimit> {
+        let mut response = self.send(Method::Get, "https://api.github.com/rate_limit").await?;
+        if response.status() != StatusCode::Ok {
+            return Err(GHError::from_response(&response).into());
+        }
+        let limit: RateLimitResources = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+        Ok(GHRateLimit {
+            remaining: limit.resources.core.remaining,
+            reset: Utc.timestamp_opt(limit.resources.core.reset, 0).single(),
+        })
+    }
+
+    /// Estimate the requests of crawling `org` from its member count and the repository pages
+    /// of a sample of its members.
+    ///
+    /// Costs a `HEAD` request for the member count, the first member page and a `HEAD` request
+    /// per sampled member.
+    #[tracing::instrument(skip(self))]
+    pub async fn estimate_org_crawl(&self, org: &str) -> Result<GHCrawlEstimate> {
+        // with a single member per page the last page is the member count
+        let response = self.send(Method::Head, &format!("https://api.github.com/orgs/{org}/members?per_page=1")).await?;
+        let count = GHClient::last_page(response)?;
+        let first_page = self.get_org_members_page(org, 1).await?;
+        let members = if first_page.is_empty() { 0 } else { count };
+
+        let sample = &first_page[..first_page.len().min(SAMPLED_MEMBERS)];
+        let pages = self.count_repository_pages(sample).await;
+        let repository_pages = extrapolate_pages(members, &pages);
+        self.finish_estimate(org, members, sample.len(), repository_pages, 2 + sample.len()).await
+    }
+
+    /// Estimate the requests of crawling `org` exactly, counting the repository pages of every
+    /// member.
+    ///
+    /// Costs the member pages plus one `HEAD` request per member, roughly half of a crawl of an
+    /// organization whose members have a single page of repositories each.
+    #[tracing::instrument(skip(self))]
+    pub async fn estimate_org_crawl_exact(&self, org: &str) -> Result<GHCrawlEstimate> {
+        let members = self.get_org_members(org).await?;
+        let repository_pages = self.count_repository_pages(&members).await.iter().sum();
+        // fetching the members like a crawl, plus a HEAD request per member
+        let estimate_requests = 1 + member_pages(members.len()) + members.len();
+        self.finish_estimate(org, members.len(), members.len(), repository_pages, estimate_requests).await
+    }
+
+    /// Repository pages of each of `users`, assuming one for those that cannot be counted.
+    async fn count_repository_pages(&self, users: &[GHUser]) -> Vec<usize> {
+        ::futures::future::join_all(users.iter().map(|user| async move {
+            let url = format!("https://api.github.com/users/{}/repos?per_page=30", user.login);
+            match self.send(Method::Head, &url).await.and_then(GHClient::last_page) {
+                Ok(pages) => pages,
+                Err(msg) => {
+                    tracing::warn!("Failed to count repository pages of {}, assuming one: {msg}", user.login);
+                    1
+                }
+            }
+        }))
+        .await
+    }
+
+    async fn finish_estimate(
+        &self,
+        org: &str,
+        members: usize,
+        sampled_members: usize,
+        repository_pages: usize,
+        estimate_requests: usize,
+    ) -> Result<GHCrawlEstimate> {
+        let member_pages = member_pages(members);
+        let rate_limit = match self.rate_limit() {
+            Some(limit) => Some(limit),
+            None => self
+                .get_rate_limit()
+                .await
+                .map_err(|msg| tracing::warn!("Failed to get the rate-limit: {msg}"))
+                .ok(),
+        };
+        Ok(GHCrawlEstimate {
+            org: org.to_string(),
+            members,
+            member_pages,
+            repository_pages,
+            sampled_members,
+            requests: crawl_requests(member_pages, members, repository_pages),
+            estimate_requests,
+            rate_limit,
+        })
+    }
+}
+
+impl fmt::Display for GHCrawlEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sampled = if self.sampled_members < self.members { format!(" (extrapolated from {} members)", self.sampled_members) } else { String::new() };
+        writeln!(f, "{}: {} members on {} pages, {} repository pages{sampled}", self.org, self.members, self.member_pages, self.repository_pages)?;
+        writeln!(f, "full crawl: {} requests (the estimate used {})", self.requests, self.estimate_requests)?;
+        match &self.rate_limit {
+            Some(limit) => {
+                let reset = limit.reset.map(|reset| format!(" until {reset}")).unwrap_or_default();
+                let verdict = if self.fits() == Some(true) { "fits" } else { "does not fit" };
+                writeln!(f, "rate-limit: {} remaining{reset}, the crawl {verdict}", limit.remaining)
+            }
+            None => writeln!(f, "rate-limit: unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::user;
+
+    #[test]
+    fn test_member_pages() {
+        assert_eq!(1, member_pages(0));
+        assert_eq!(1, member_pages(30));
+        assert_eq!(2, member_pages(31));
+    }
+
+    #[test]
+    fn test_extrapolate_pages() {
+        assert_eq!(0, extrapolate_pages(0, &[]));
+        assert_eq!(5, extrapolate_pages(5, &[]));
+        // 1.5 pages per member
+        assert_eq!(150, extrapolate_pages(100, &[1, 2]));
+        assert_eq!(2, extrapolate_pages(1, &[1, 2]));
+        // at least a page per member
+        assert_eq!(100, extrapolate_pages(100, &[1, 1, 1]));
+    }
+
+    #[test]
+    fn test_recrawl_requests() {
+        let data = vec![(user("alice", 1), Vec::new()), (user("bob", 2), vec![GHRepository::default(); 31])];
+        // HEAD and page of the members, HEAD and one page for alice, HEAD and two pages for bob
+        assert_eq!(crawl_requests(1, 2, 3), recrawl_requests(&data));
+        assert_eq!(7, recrawl_requests(&data));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut estimate = GHCrawlEstimate {
+            org: "octo".into(),
+            members: 40,
+            member_pages: 2,
+            repository_pages: 55,
+            sampled_members: 40,
+            requests: crawl_requests(2, 40, 55),
+            estimate_requests: 43,
+            rate_limit: None,
+        };
+        assert_eq!(98, estimate.requests);
+        assert_eq!(None, estimate.fits());
+
+        estimate.rate_limit = Some(GHRateLimit { remaining: 98, reset: None });
+        assert_eq!(Some(true), estimate.fits());
+        assert!(estimate.to_string().ends_with("rate-limit: 98 remaining, the crawl fits\n"));
+        estimate.rate_limit = Some(GHRateLimit { remaining: 97, reset: None });
+        assert_eq!(Some(false), estimate.fits());
+    }
+}
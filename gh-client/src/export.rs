@@ -0,0 +1,155 @@
+//! File export of collected organization data for spreadsheets and BI tools.
+//!
+//! Every export consists of three tables, written to one file each (`<table>.<extension>`):
+//!
+//! | table            | columns                                                            |
+//! |------------------|--------------------------------------------------------------------|
+//! | `users`          | `login` (str), `id` (u64), `avatar_url` (str), `repos_url` (str)   |
+//! | `repositories`   | `login` (str), `name` (str), `language` (str, nullable),           |
+//! |                  | `stargazers_count` (u64), `pushed_at` (str, RFC 3339, nullable),   |
+//! |                  | `html_url` (str)                                                   |
+//! | `user_languages` | `login` (str), `language` (str), `count` (u64)                     |
+//!
+//! Repositories collected by older clients have 0 stars, no push time and an empty `html_url`.
+//!
+//! Tables are joined on `login`. Columns are only ever appended to keep existing imports working.
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+
+use crate::frame::{repositories_frame, user_languages_frame, users_frame};
+use crate::{GHRepository, GHUser};
+
+/// Name of the table with one row per user.
+pub const USERS_TABLE: &str = "users";
+/// Name of the table with one row per repository.
+pub const REPOSITORIES_TABLE: &str = "repositories";
+/// Name of the table with repository counts per user and language.
+pub const USER_LANGUAGES_TABLE: &str = "user_languages";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "ndjson" | "json-lines" => Ok(ExportFormat::JsonLines),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(anyhow!("Unknown export format {s}, expected one of csv, jsonl or parquet")),
+        }
+    }
+}
+
+/// Write a single table in the given format.
+pub fn write_frame<W: Write>(frame: &mut DataFrame, format: ExportFormat, writer: W) -> Result<()> {
+    match format {
+        ExportFormat::Csv => CsvWriter::new(writer).has_header(true).finish(frame)?,
+        ExportFormat::JsonLines => JsonWriter::new(writer).with_json_format(JsonFormat::JsonLines).finish(frame)?,
+        ExportFormat::Parquet => ParquetWriter::new(writer).finish(frame)?,
+    }
+    Ok(())
+}
+
+/// Write the users, repositories and user-language tables into `directory`.
+///
+/// Existing files are overwritten. Returns the paths of the written files.
+pub fn export_tables(user_repos: &[(GHUser, Vec<GHRepository>)], directory: &Path, format: ExportFormat) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(directory)?;
+    let tables = [
+        (USERS_TABLE, users_frame(user_repos)?),
+        (REPOSITORIES_TABLE, repositories_frame(user_repos)?),
+        (USER_LANGUAGES_TABLE, user_languages_frame(user_repos)?),
+    ];
+
+    let mut paths = Vec::with_capacity(tables.len());
+    for (name, mut frame) in tables {
+        let path = directory.join(format!("{name}.{}", format.extension()));
+        tracing::info!("writing {name} table to {}", path.display());
+        write_frame(&mut frame, format, File::create(&path)?)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn frame() -> DataFrame {
+        df!(
+            "login" => ["alice", "bob"],
+            "language" => [Some("Rust"), None],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_write_csv() -> Result<()> {
+        let mut buffer = Vec::new();
+        write_frame(&mut frame(), ExportFormat::Csv, &mut buffer)?;
+        assert_eq!("login,language\nalice,Rust\nbob,\n", String::from_utf8(buffer)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_lines() -> Result<()> {
+        let mut buffer = Vec::new();
+        write_frame(&mut frame(), ExportFormat::JsonLines, &mut buffer)?;
+        let lines: Vec<serde_json::Value> = String::from_utf8(buffer)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(2, lines.len());
+        assert_eq!("alice", lines[0]["login"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_parquet() -> Result<()> {
+        let mut buffer = Vec::new();
+        write_frame(&mut frame(), ExportFormat::Parquet, &mut buffer)?;
+        assert_eq!(b"PAR1", &buffer[..4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repositories_csv_columns() -> Result<()> {
+        let data = vec![(fixtures::user("alice", 1), vec![fixtures::repo("a", Some("Rust"))])];
+        let mut buffer = Vec::new();
+        write_frame(&mut repositories_frame(&data)?, ExportFormat::Csv, &mut buffer)?;
+        let csv = String::from_utf8(buffer)?;
+        assert_eq!(Some("login,name,language,stargazers_count,pushed_at,html_url"), csv.lines().next());
+        assert_eq!(Some("alice,a,Rust,0,,\"\""), csv.lines().nth(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(ExportFormat::JsonLines, "JSONL".parse::<ExportFormat>().unwrap());
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+}
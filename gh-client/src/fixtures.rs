@@ -0,0 +1,20 @@
+//! Factories for users and repositories shared by the tests of this workspace.
+//!
+//! Available in the tests of gh-client and, through the `test-util` feature, in dependent crates.
+
+use crate::{GHRepository, GHUser};
+
+/// User with the API urls GitHub would return for `login` and `id`.
+pub fn user(login: &str, id: usize) -> GHUser {
+    GHUser {
+        login: login.into(),
+        id,
+        repos_url: format!("https://api.github.com/users/{login}/repos"),
+        avatar_url: format!("https://avatars.githubusercontent.com/u/{id}"),
+    }
+}
+
+/// Repository with only a name and a language set.
+pub fn repo(name: &str, language: Option<&str>) -> GHRepository {
+    GHRepository { name: name.into(), language: language.map(Into::into), ..Default::default() }
+}
@@ -0,0 +1,100 @@
+//! Conversion of collected organization data into normalized polars [DataFrame]s.
+//!
+//! All functions take the `(user, repositories)` pairs as returned by crawling an organization
+//! and produce one table each, which can be joined on the `login` column.
+use polars::prelude::*;
+
+use crate::{language_count, GHRepository, GHUser};
+
+/// One row per user with the columns `login`, `id`, `avatar_url` and `repos_url`.
+pub fn users_frame(user_repos: &[(GHUser, Vec<GHRepository>)]) -> PolarsResult<DataFrame> {
+    let users = user_repos.iter().map(|(user, _)| user);
+    DataFrame::new(vec![
+        Series::new("login", users.clone().map(|user| user.login.clone()).collect::<Vec<_>>()),
+        Series::new("id", users.clone().map(|user| user.id as u64).collect::<Vec<_>>()),
+        Series::new("avatar_url", users.clone().map(|user| user.avatar_url.clone()).collect::<Vec<_>>()),
+        Series::new("repos_url", users.map(|user| user.repos_url.clone()).collect::<Vec<_>>()),
+    ])
+}
+
+/// One row per repository with the columns `login` (owner), `name`, `language` (nullable),
+/// `stargazers_count`, `pushed_at` (RFC 3339, nullable) and `html_url`.
+pub fn repositories_frame(user_repos: &[(GHUser, Vec<GHRepository>)]) -> PolarsResult<DataFrame> {
+    let repos: Vec<(&GHUser, &GHRepository)> = user_repos
+        .iter()
+        .flat_map(|(user, repos)| repos.iter().map(move |repo| (user, repo)))
+        .collect();
+    DataFrame::new(vec![
+        Series::new("login", repos.iter().map(|(user, _)| user.login.clone()).collect::<Vec<_>>()),
+        Series::new("name", repos.iter().map(|(_, repo)| repo.name.clone()).collect::<Vec<_>>()),
+        Series::new("language", repos.iter().map(|(_, repo)| repo.language.clone()).collect::<Vec<_>>()),
+        Series::new("stargazers_count", repos.iter().map(|(_, repo)| repo.stargazers_count as u64).collect::<Vec<_>>()),
+        Series::new("pushed_at", repos.iter().map(|(_, repo)| repo.pushed_at.map(|pushed_at| pushed_at.to_rfc3339())).collect::<Vec<_>>()),
+        Series::new("html_url", repos.iter().map(|(_, repo)| repo.html_url.clone()).collect::<Vec<_>>()),
+    ])
+}
+
+/// One row per user and language with the columns `login`, `language` and `count`, where `count`
+/// is the number of repositories of the user in that language. Repositories without a language
+/// are skipped, rows are sorted by login and language.
+pub fn user_languages_frame(user_repos: &[(GHUser, Vec<GHRepository>)]) -> PolarsResult<DataFrame> {
+    let mut rows: Vec<(&str, String, u64)> = user_repos
+        .iter()
+        .flat_map(|(user, repos)| {
+            language_count(repos)
+                .into_iter()
+                .map(move |(language, count)| (user.login.as_str(), language, count as u64))
+        })
+        .collect();
+    rows.sort();
+    DataFrame::new(vec![
+        Series::new("login", rows.iter().map(|(login, _, _)| *login).collect::<Vec<_>>()),
+        Series::new("language", rows.iter().map(|(_, language, _)| language.as_str()).collect::<Vec<_>>()),
+        Series::new("count", rows.iter().map(|(_, _, count)| *count).collect::<Vec<_>>()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{repo, user};
+
+    fn user_repos() -> Vec<(GHUser, Vec<GHRepository>)> {
+        vec![
+            (user("alice", 1), vec![repo("a", Some("Rust")), repo("b", Some("Rust")), repo("c", None)]),
+            (user("bob", 2), vec![repo("d", Some("Python")), repo("e", Some("Rust"))]),
+        ]
+    }
+
+    #[test]
+    fn test_frame_shapes() -> PolarsResult<()> {
+        let data = user_repos();
+        assert_eq!((2, 4), users_frame(&data)?.shape());
+        assert_eq!((5, 6), repositories_frame(&data)?.shape());
+        assert_eq!((3, 3), user_languages_frame(&data)?.shape());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repositories_columns() -> PolarsResult<()> {
+        let mut data = user_repos();
+        data[0].1[0].stargazers_count = 3;
+        data[0].1[0].pushed_at = chrono::DateTime::from_timestamp(0, 0);
+        let frame = repositories_frame(&data)?;
+        assert_eq!(vec!["login", "name", "language", "stargazers_count", "pushed_at", "html_url"], frame.get_column_names());
+        assert_eq!(Some(3), frame.column("stargazers_count")?.u64()?.get(0));
+        assert_eq!(Some("1970-01-01T00:00:00+00:00"), frame.column("pushed_at")?.utf8()?.get(0));
+        assert_eq!(None, frame.column("pushed_at")?.utf8()?.get(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_languages_counts() -> PolarsResult<()> {
+        let frame = user_languages_frame(&user_repos())?;
+        let counts: Vec<Option<u64>> = frame.column("count")?.u64()?.into_iter().collect();
+        assert_eq!(vec![Some(2), Some(1), Some(1)], counts);
+        let languages: Vec<Option<&str>> = frame.column("language")?.utf8()?.into_iter().collect();
+        assert_eq!(vec![Some("Rust"), Some("Python"), Some("Rust")], languages);
+        Ok(())
+    }
+}
@@ -3,8 +3,30 @@ use regex::Regex;
 use surf::{Client, Request, Response, StatusCode};
 use surf::http::Method;
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::crawl::{CancellationToken, CrawlProgress};
+use crate::metrics::Metrics;
+
+pub mod crawl;
+pub mod diff;
+pub mod estimate;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixtures;
+pub mod frame;
+pub mod metrics;
+pub mod snapshot;
+pub mod stats;
+#[cfg(feature = "sqlite")]
+pub mod storage;
+#[cfg(feature = "sqlite")]
+pub mod sync;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct GHUser {
     pub login: String,
     pub id: usize,
@@ -12,46 +34,353 @@ pub struct GHUser {
     pub avatar_url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GHOrganization {
+    pub login: String,
+    pub id: usize,
+    pub avatar_url: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
 pub struct GHRepository {
     pub name: String,
     pub language: Option<String>,
+    /// Time of the last push, missing in data collected by older clients.
+    #[serde(default)]
+    pub pushed_at: Option<DateTime<Utc>>,
+    /// Missing (0) in data collected by older clients.
+    #[serde(default)]
+    pub stargazers_count: usize,
+    /// Page of the repository on GitHub, empty in data collected by older clients.
+    #[serde(default)]
+    pub html_url: String,
+}
+
+/// Group repositories by language and return counts for every language.
+///
+/// Repositories without a detected language are skipped.
+pub fn language_count(repositories: &[GHRepository]) -> HashMap<String, usize> {
+    let mut map: HashMap<String, usize> = HashMap::new();
+    for repo in repositories {
+        if let Some(language) = &repo.language {
+            *map.entry(language.clone()).or_insert(0) += 1;
+        }
+    }
+    map
+}
+
+/// Failure of a GH-API request, wrapped in [anyhow::Error] by the client methods.
+///
+/// Downcast to it to tell e.g. missing users from network problems.
+#[derive(Debug)]
+pub enum GHError {
+    /// The request could not be sent or no response was received.
+    Transport(String),
+    /// The response has an unexpected status.
+    Status(StatusCode),
+    /// The response has a `403 Forbidden` or `429 Too Many Requests` status because of the
+    /// primary or a secondary rate-limit.
+    RateLimited(StatusCode),
+    /// The response body could not be read or parsed.
+    Body(String),
+}
+
+impl std::fmt::Display for GHError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GHError::Transport(msg) => write!(f, "Failed sending request: {msg}"),
+            GHError::Status(status) => write!(f, "Request failed with {status}"),
+            GHError::RateLimited(status) => write!(f, "Request was rate-limited with {status}"),
+            GHError::Body(msg) => write!(f, "Failed reading body: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for GHError {}
+
+impl GHError {
+    /// Error of a response with an unexpected status, telling rate-limits from other refusals
+    /// by the `X-RateLimit-Remaining` and `Retry-After` headers.
+    pub fn from_response(response: &Response) -> Self {
+        let status = response.status();
+        let exhausted = response.header("X-RateLimit-Remaining").is_some_and(|remaining| remaining.as_str() == "0");
+        let retry_after = response.header("Retry-After").is_some();
+        match status {
+            StatusCode::Forbidden | StatusCode::TooManyRequests if exhausted || retry_after => GHError::RateLimited(status),
+            status => GHError::Status(status),
+        }
+    }
+}
+
+/// Result of a conditional request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GHConditional<T> {
+    /// The resource did not change since the request that returned the given ETag.
+    NotModified,
+    /// The current value together with the ETag to pass on the next request.
+    Modified { value: T, etag: Option<String> },
+}
+
+/// Owner, scopes and expiry of the token a [GHClient] uses, as reported by the GH-API.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GHTokenInfo {
+    /// Login of the user the token belongs to.
+    pub login: String,
+    /// Scopes from the `X-OAuth-Scopes` header. `None` for fine-grained tokens, which do not report scopes.
+    pub scopes: Option<Vec<String>>,
+    /// Expiry from the `GitHub-Authentication-Token-Expiration` header. `None` if the token does not expire.
+    pub expires_at: Option<String>,
+}
+
+impl GHTokenInfo {
+    /// Whether the token may list concealed (non-public) organization members.
+    ///
+    /// Fine-grained tokens do not report their permissions, so they get the benefit of the doubt.
+    pub fn can_read_org(&self) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.iter().any(|s| matches!(s.as_str(), "read:org" | "write:org" | "admin:org")),
+            None => true,
+        }
+    }
+}
+
+/// SAML single sign-on status of a response, taken from the `X-GitHub-SSO` header.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GHSso {
+    /// The token has to be authorized for the organization by visiting `url`.
+    Required { url: String },
+    /// The response lacks data of the organizations with the given ids.
+    PartialResults { organizations: Vec<String> },
+}
+
+impl GHSso {
+    /// Parse a header value like `required; url=https://...` or `partial-results; organizations=1,2`.
+    fn parse(header: &str) -> Option<Self> {
+        let (kind, value) = header.split_once(';').unwrap_or((header, ""));
+        let value = value.trim();
+        match kind.trim() {
+            "required" => Some(GHSso::Required { url: value.strip_prefix("url=").unwrap_or(value).to_string() }),
+            "partial-results" => Some(GHSso::PartialResults {
+                organizations: value
+                    .strip_prefix("organizations=")
+                    .unwrap_or(value)
+                    .split(',')
+                    .filter(|id| !id.is_empty())
+                    .map(|id| id.trim().to_string())
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn from_response(response: &Response) -> Option<Self> {
+        response.header("X-GitHub-SSO").and_then(|header| GHSso::parse(header.as_str()))
+    }
+}
+
+/// Whether a token gives access to the complete member list of an organization.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GHOrgAccess {
+    pub org: String,
+    pub token: GHTokenInfo,
+    pub sso: Option<GHSso>,
+}
+
+impl GHOrgAccess {
+    /// Human readable reasons why data fetched for the organization will be incomplete.
+    /// Empty if the token sees everything.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.token.can_read_org() {
+            warnings.push(format!(
+                "Token of {} lacks the read:org scope, only public members of {} will be listed.",
+                self.token.login, self.org
+            ));
+        }
+        match &self.sso {
+            Some(GHSso::Required { url }) => warnings.push(format!(
+                "Token is not SSO-authorized for {}, only public members will be listed. Authorize it at {url}",
+                self.org
+            )),
+            Some(GHSso::PartialResults { .. }) => warnings.push(format!(
+                "Token is not SSO-authorized for {}, results will be incomplete.",
+                self.org
+            )),
+            None => {}
+        }
+        warnings
+    }
+}
+
+/// A token of the pool together with the rate-limit budget the GH-API last reported for it.
+#[derive(Debug, Clone)]
+struct GHToken {
+    token: String,
+    /// `X-RateLimit-Remaining` of the last response, `None` until the first response.
+    remaining: Option<usize>,
+    /// `X-RateLimit-Reset` of the last response in unix seconds.
+    reset: Option<i64>,
+}
+
+impl GHToken {
+    /// Remaining requests, assuming the full budget if unknown or the rate-limit window passed.
+    fn budget(&self, now: i64) -> usize {
+        match (self.remaining, self.reset) {
+            (Some(remaining), Some(reset)) if reset > now => remaining,
+            _ => usize::MAX,
+        }
+    }
+}
+
+/// Rate-limit budget of all tokens of a [GHClient].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GHRateLimit {
+    /// Remaining requests summed over all tokens.
+    pub remaining: usize,
+    /// Earliest end of the rate-limit window of a token.
+    pub reset: Option<DateTime<Utc>>,
 }
 
 pub struct GHClient {
-    token: Option<String>,
+    tokens: Mutex<Vec<GHToken>>,
     client: Client,
+    metrics: Arc<Metrics>,
 }
 
 // FIXME: turn this into a trait and provide blanket implementations for tower::Service<HTTPRequest>
 //        see: https://docs.rs/tower-http/latest/tower_http/index.html#example-client
 impl GHClient {
-    pub fn new(client: Client, token: Option<String>) -> Self { Self { client, token } }
+    pub fn new(client: Client, token: Option<String>) -> Self { Self::with_tokens(client, token.into_iter().collect()) }
+
+    /// Create a client rotating between a pool of tokens.
+    ///
+    /// Every request uses the token with the largest remaining rate-limit budget. Requests are
+    /// only paused once all tokens are exhausted, until the earliest of them is reset.
+    pub fn with_tokens(client: Client, tokens: Vec<String>) -> Self {
+        let tokens = tokens.into_iter().map(|token| GHToken { token, remaining: None, reset: None }).collect();
+        Self { client, tokens: Mutex::new(tokens), metrics: Arc::default() }
+    }
 
     /// Build a request including the token (if available) and CORS Mode.
-    fn request(&self, method: Method, url: &str) -> Request {
+    fn request(method: Method, url: &str, token: Option<&str>) -> Request {
         let mut request = Request::new(method, url.try_into().unwrap());
         request.set_header("Accept", "application/vnd.github.v3+json");
         // fixme: rework the header injection via the tower_http service layers.
         request.set_header("User-Agent", "Awesome-Octocat-App");
 
         // any nice functional way to incorporate this in the above builder pattern?
-        if let Some(token) = &self.token {
-            request.set_header("Authorization", &format!("Bearer {}", token));
+        if let Some(token) = token {
+            request.set_header("Authorization", format!("Bearer {}", token));
         }
         request
     }
 
+    /// Pick the token with the largest remaining budget.
+    ///
+    /// Returns the index of the token (`None` without any token) or, if all tokens are exhausted,
+    /// the number of seconds until the earliest reset.
+    fn next_token(&self) -> std::result::Result<Option<(usize, String)>, i64> {
+        let now = chrono::Utc::now().timestamp();
+        let tokens = self.tokens.lock().unwrap();
+        match tokens.iter().enumerate().max_by_key(|(_, token)| token.budget(now)) {
+            None => Ok(None),
+            Some((index, token)) if token.budget(now) > 0 => Ok(Some((index, token.token.clone()))),
+            Some(_) => Err(tokens.iter().filter_map(|token| token.reset).min().unwrap_or(now) - now + 1),
+        }
+    }
+
+    /// Metrics of the requests sent by this client.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Remaining budget as of the last responses, `None` without tokens or before every token got
+    /// a response.
+    pub fn rate_limit(&self) -> Option<GHRateLimit> {
+        let now = chrono::Utc::now().timestamp();
+        let tokens = self.tokens.lock().unwrap();
+        if tokens.is_empty() || tokens.iter().any(|token| token.budget(now) == usize::MAX) {
+            return None;
+        }
+        Some(GHRateLimit {
+            remaining: tokens.iter().map(|token| token.budget(now)).sum(),
+            reset: tokens
+                .iter()
+                .filter_map(|token| token.reset)
+                .min()
+                .and_then(|reset| Utc.timestamp_opt(reset, 0).single()),
+        })
+    }
+
+    /// Record the rate-limit headers of a response for the token it was sent with.
+    fn record_rate_limit(&self, index: usize, response: &Response) {
+        let header = |name: &str| response.header(name).and_then(|value| value.as_str().parse::<i64>().ok());
+        let mut tokens = self.tokens.lock().unwrap();
+        let token = &mut tokens[index];
+        if let Some(remaining) = header("X-RateLimit-Remaining") {
+            token.remaining = Some(remaining as usize);
+            self.metrics.record_rate_limit(index, remaining as usize);
+        }
+        if let Some(reset) = header("X-RateLimit-Reset") {
+            token.reset = Some(reset);
+        }
+    }
+
+    /// Send a request with the best token of the pool, waiting for a rate-limit reset if all
+    /// tokens are exhausted.
+    async fn send(&self, method: Method, url: &str) -> Result<Response> {
+        self.send_with_headers(method, url, &[]).await
+    }
+
+    /// Like [GHClient::send], with additional request headers.
+    #[tracing::instrument(level = "debug", skip(self, url, headers), fields(endpoint = %metrics::endpoint(url), status))]
+    async fn send_with_headers(&self, method: Method, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        loop {
+            let token = match self.next_token() {
+                Ok(token) => token,
+                Err(seconds) => {
+                    tracing::warn!("All tokens exhausted their rate-limit, pausing for {seconds}s");
+                    futures_timer::Delay::new(std::time::Duration::from_secs(seconds.max(1) as u64)).await;
+                    continue;
+                }
+            };
+            let mut request = GHClient::request(method, url, token.as_ref().map(|(_, token)| token.as_str()));
+            for (name, value) in headers {
+                request.set_header(*name, *value);
+            }
+            let start = Utc::now();
+            let response = self.client.send(request).await;
+            let seconds = (Utc::now() - start).num_milliseconds() as f64 / 1000.0;
+            self.metrics.record_request(url, response.as_ref().ok().map(|response| response.status() as u16), seconds);
+            let response = response.map_err(|e| GHError::Transport(format!("{e:?}")))?;
+            tracing::Span::current().record("status", response.status() as u16);
+
+            if let Some((index, _)) = token {
+                self.record_rate_limit(index, &response);
+                // a token that ran dry in the meantime is retried with the next one of the pool
+                let limited = matches!(response.status(), StatusCode::Forbidden | StatusCode::TooManyRequests);
+                if limited && response.header("X-RateLimit-Remaining").map(|v| v.as_str() == "0").unwrap_or(false) {
+                    tracing::info!("Token {index} hit its rate-limit, retrying with another token");
+                    self.metrics.record_retry();
+                    continue;
+                }
+            }
+            return Ok(response);
+        }
+    }
+
     /// extract the last page number from the response headers.
     /// Note: Pagination is "one based" - I.e. a range 1..last_page + 1 in the GH API.
     fn last_page(response: Response) -> Result<usize> {
         // Note: in the API query parameters aren't zero based!
         match response.header("link") {
             Some(pagination_header) => {
-                log::debug!("pagination header: {pagination_header}");
+                tracing::debug!("pagination header: {pagination_header}");
                 let re = Regex::new(r".*&page=([0-9]+).*").expect("Failed to construct regex");
                 let last_page: usize = re
-                    .captures_iter(&pagination_header.to_string())
+                    .captures_iter(pagination_header.as_str())
                     // .map(|c| {
                     //     println!("match: {c:?}");
                     //     c
@@ -59,41 +388,97 @@ impl GHClient {
                     .last()
                     .ok_or(anyhow!("Could not parse pagination header"))?[1]
                     .parse::<usize>()?;
-                log::debug!("last page from header: {last_page}");
+                tracing::debug!("last page from header: {last_page}");
                 Ok(last_page)
             }
             None => {
-                log::debug!("no page header found. assuming single page.");
+                tracing::debug!("no page header found. assuming single page.");
                 Ok(1)
             }
         }
     }
 
+    /// Validate the token and report its owner, scopes and expiry.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_token_info(&self) -> Result<GHTokenInfo> {
+        if self.tokens.lock().unwrap().is_empty() {
+            return Err(anyhow!("No token configured"));
+        }
+        let mut response = self.send(Method::Get, "https://api.github.com/user").await?;
+        if response.status() != StatusCode::Ok {
+            return Err(GHError::from_response(&response).into());
+        }
+
+        let scopes = response.header("X-OAuth-Scopes").map(|header| {
+            header
+                .as_str()
+                .split(',')
+                .map(str::trim)
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_string)
+                .collect()
+        });
+        let expires_at = response.header("GitHub-Authentication-Token-Expiration").map(|header| header.to_string());
+        let user: GHUser = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+        Ok(GHTokenInfo { login: user.login, scopes, expires_at })
+    }
+
+    /// Organizations of the token's user.
+    ///
+    /// Without the `read:org` scope only organizations with public membership are listed.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_user_orgs(&self) -> Result<Vec<GHOrganization>> {
+        let mut response = self.send(Method::Get, "https://api.github.com/user/orgs?per_page=100").await?;
+        if response.status() != StatusCode::Ok {
+            return Err(GHError::from_response(&response).into());
+        }
+        let orgs: Vec<GHOrganization> = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+        Ok(orgs)
+    }
+
+    /// Check whether the token can see all members of an organization, i.e. has the `read:org`
+    /// scope and is SSO-authorized for it.
+    #[tracing::instrument(skip(self))]
+    pub async fn check_org_access(&self, org: &str) -> Result<GHOrgAccess> {
+        let token = self.get_token_info().await?;
+        let response = self.send(Method::Head, &format!("https://api.github.com/orgs/{org}/members?per_page=30")).await?;
+        let sso = GHSso::from_response(&response);
+        if sso.is_none() && response.status() != StatusCode::Ok {
+            return Err(GHError::from_response(&response).into());
+        }
+        let access = GHOrgAccess { org: org.to_string(), token, sso };
+        for warning in access.warnings() {
+            tracing::warn!("{warning}");
+        }
+        Ok(access)
+    }
+
     /// Get a single page of organization members.
+    #[tracing::instrument(level = "debug", skip(self))]
     async fn get_org_members_page(&self, org: &str, page: usize) -> Result<Vec<GHUser>> {
-        log::debug!("fetching {org}-org member page {page}");
-        let request = self.request(
-            Method::Get,
-            &format!("https://api.github.com/orgs/{org}/members?per_page=30&page={page}"),
-        );
-
-        let mut response = self.client.send(request).await.map_err(|e| anyhow!("Failed sending request: {e:?}"))?;
-        let members: Vec<GHUser> = response.body_json().await.map_err(|e| anyhow!("Failed reading body: {e:?}"))?;
+        tracing::debug!("fetching {org}-org member page {page}");
+        let mut response = self
+            .send(Method::Get, &format!("https://api.github.com/orgs/{org}/members?per_page=30&page={page}"))
+            .await?;
+        if response.status() != StatusCode::Ok {
+            return Err(GHError::from_response(&response).into());
+        }
+        let members: Vec<GHUser> = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+        self.metrics.record_items("members", members.len());
         Ok(members)
     }
 
     /// Get all members of an organization.
+    #[tracing::instrument(skip(self))]
     pub async fn get_org_members(&self, org: &str) -> Result<Vec<GHUser>> {
-        log::info!("fetching organization members of {org}");
-
-        let request = self.request(
-            surf::http::Method::Head,
-            &format!("https://api.github.com/orgs/{org}/members?per_page=30"),
-        );
+        tracing::info!("fetching organization members of {org}");
 
         // get the link header
         // link: <.../{org}/members?page=2>; rel="next", <...{org}/members?page=123>; rel="last"
-        let response = self.client.send(request).await.map_err(|e| anyhow!("Failed sending request: {e:?}"))?;
+        let response = self.send(Method::Head, &format!("https://api.github.com/orgs/{org}/members?per_page=30")).await?;
+        if let Some(sso) = GHSso::from_response(&response) {
+            tracing::warn!("Member list of {org} will be incomplete, token is not SSO-authorized: {sso:?}");
+        }
 
         // Note: in the API query parameters aren't zero based!
         let last_page: usize = GHClient::last_page(response)?;
@@ -105,38 +490,34 @@ impl GHClient {
             let mut pages = self.get_org_members_page(org, page).await?;
             users.append(&mut pages)
         }
-        log::debug!("Loaded {0} users for {org}", users.len());
+        tracing::debug!("Loaded {0} users for {org}", users.len());
 
-        return Ok(users);
+        Ok(users)
     }
 
     /// Get a single page of the repositories of a user.
+    #[tracing::instrument(level = "debug", skip(self, user), fields(login = user))]
     async fn get_user_repositories_page(&self, user: &str, page: usize) -> Result<Vec<GHRepository>> {
-        log::debug!("fetching {user} repository page {page}");
-        let request = self.request(
-            Method::Get,
-            &format!("https://api.github.com/users/{user}/repos?per_page=30&page={page}"),
-        );
-        let mut response = self.client.send(request).await.map_err(|e| anyhow!("Failed sending request: {e:?}"))?;
+        tracing::debug!("fetching {user} repository page {page}");
+        let mut response = self
+            .send(Method::Get, &format!("https://api.github.com/users/{user}/repos?per_page=30&page={page}"))
+            .await?;
         if response.status() != StatusCode::Ok {
-            return Err(anyhow!("Request failed with {}", response.status()));
+            return Err(GHError::from_response(&response).into());
         }
 
-        let repos: Vec<GHRepository> = response.body_json().await.map_err(|e| anyhow!("Failed reading body: {e:?}"))?;
+        let repos: Vec<GHRepository> = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+        self.metrics.record_items("repositories", repos.len());
         Ok(repos)
     }
 
+    #[tracing::instrument(skip(self, user), fields(login = user))]
     pub async fn get_user_repositories(&self, user: &str) -> Result<Vec<GHRepository>> {
-        log::info!("fetching user repositories for {user}");
-
-        let request = self.request(
-            Method::Head,
-            &format!("https://api.github.com/users/{user}/repos?per_page=30"),
-        );
+        tracing::info!("fetching user repositories for {user}");
 
         // get the link header
         // link: <.../{org}/members?page=2>; rel="next", <...{org}/members?page=123>; rel="last"
-        let response = self.client.send(request).await.map_err(|e| anyhow!("Failed sending request: {e:?}"))?;
+        let response = self.send(Method::Head, &format!("https://api.github.com/users/{user}/repos?per_page=30")).await?;
         let last_page: usize = GHClient::last_page(response)?;
 
         let mut repos: Vec<GHRepository> = Vec::with_capacity(last_page * 30);
@@ -145,18 +526,79 @@ impl GHClient {
         for page in pages.await {
             repos.append(&mut page?);
         }
-        log::debug!("Loaded {0} repos for {user}", repos.len());
-        return Ok(repos);
+        tracing::debug!("Loaded {0} repos for {user}", repos.len());
+        Ok(repos)
+    }
+
+    /// Get all repositories of a user, the most recently pushed first.
+    ///
+    /// With the `etag` of a previous call, the request is conditional and returns
+    /// [GHConditional::NotModified] if the repositories did not change since, neither by pushes
+    /// nor by e.g. stars. The ETag only covers the first page of 100 repositories, so none is
+    /// returned for users with more, whose repositories are always fetched completely.
+    #[tracing::instrument(level = "debug", skip(self, user, etag), fields(login = user))]
+    pub async fn get_user_repositories_if_modified(&self, user: &str, etag: Option<&str>) -> Result<GHConditional<Vec<GHRepository>>> {
+        tracing::debug!("fetching repositories of {user} if modified");
+        let url = format!("https://api.github.com/users/{user}/repos?sort=pushed&direction=desc&per_page=100");
+        let headers: Vec<(&str, &str)> = etag.map(|etag| ("If-None-Match", etag)).into_iter().collect();
+        let mut response = self.send_with_headers(Method::Get, &url, &headers).await?;
+        match response.status() {
+            StatusCode::NotModified => Ok(GHConditional::NotModified),
+            // a link header points to further pages
+            StatusCode::Ok if response.header("link").is_some() => {
+                Ok(GHConditional::Modified { value: self.get_user_repositories(user).await?, etag: None })
+            }
+            StatusCode::Ok => {
+                let etag = response.header("ETag").map(|header| header.to_string());
+                let repos: Vec<GHRepository> = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+                self.metrics.record_items("repositories", repos.len());
+                Ok(GHConditional::Modified { value: repos, etag })
+            }
+            _ => Err(GHError::from_response(&response).into()),
+        }
+    }
+
+    /// Get the push time of the most recently pushed repository of a user.
+    ///
+    /// With the `etag` of a previous call, the request is conditional and returns
+    /// [GHConditional::NotModified] if nothing changed since. Such requests do not count against
+    /// the rate-limit.
+    #[tracing::instrument(level = "debug", skip(self, user, etag), fields(login = user))]
+    pub async fn get_latest_push(&self, user: &str, etag: Option<&str>) -> Result<GHConditional<Option<DateTime<Utc>>>> {
+        tracing::debug!("fetching latest push of {user}");
+        let url = format!("https://api.github.com/users/{user}/repos?sort=pushed&direction=desc&per_page=1");
+        let headers: Vec<(&str, &str)> = etag.map(|etag| ("If-None-Match", etag)).into_iter().collect();
+        let mut response = self.send_with_headers(Method::Get, &url, &headers).await?;
+        match response.status() {
+            StatusCode::NotModified => Ok(GHConditional::NotModified),
+            StatusCode::Ok => {
+                let etag = response.header("ETag").map(|header| header.to_string());
+                let repos: Vec<GHRepository> = response.body_json().await.map_err(|e| GHError::Body(format!("{e:?}")))?;
+                Ok(GHConditional::Modified { value: repos.first().and_then(|repo| repo.pushed_at), etag })
+            }
+            _ => Err(GHError::from_response(&response).into()),
+        }
+    }
+
+    /// Get all members of an organization together with their repositories.
+    ///
+    /// Repositories of all members are fetched concurrently. Members whose repositories cannot
+    /// be fetched are reported in [CrawlProgress::failures] of the returned, then incomplete,
+    /// crawl.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_org_member_repositories(&self, org: &str) -> Result<CrawlProgress> {
+        self.crawl_org(CrawlProgress::new(org), &CancellationToken::default(), |_| {}).await
     }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use surf::Client;
-    use crate::GHClient;
+    use surf::{Client, StatusCode};
+    use crate::{GHClient, GHError, GHOrgAccess, GHRateLimit, GHSso, GHTokenInfo};
     use rstest::*;
     use anyhow::Result;
+    use chrono::{TimeZone, Utc};
 
     #[fixture]
     fn token() -> String {
@@ -194,4 +636,81 @@ mod tests {
         assert_eq!(145, repos.len());
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_token_rotation() {
+        let client = GHClient::with_tokens(Client::new(), vec!["a".into(), "b".into()]);
+        let reset = chrono::Utc::now().timestamp() + 60;
+        {
+            let mut tokens = client.tokens.lock().unwrap();
+            tokens[0].remaining = Some(10);
+            tokens[0].reset = Some(reset);
+            tokens[1].remaining = Some(20);
+            tokens[1].reset = Some(reset);
+        }
+        assert_eq!(Ok(Some((1, "b".to_string()))), client.next_token());
+
+        client.tokens.lock().unwrap()[1].remaining = Some(0);
+        assert_eq!(Ok(Some((0, "a".to_string()))), client.next_token());
+
+        client.tokens.lock().unwrap()[0].remaining = Some(0);
+        assert!(matches!(client.next_token(), Err(seconds) if seconds > 0 && seconds <= 61));
+
+        // an expired rate-limit window restores the full budget
+        client.tokens.lock().unwrap()[0].reset = Some(reset - 120);
+        assert_eq!(Ok(Some((0, "a".to_string()))), client.next_token());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let client = GHClient::with_tokens(Client::new(), vec!["a".into(), "b".into()]);
+        assert_eq!(None, client.rate_limit());
+        let reset = chrono::Utc::now().timestamp() + 60;
+        {
+            let mut tokens = client.tokens.lock().unwrap();
+            tokens[0].remaining = Some(0);
+            tokens[0].reset = Some(reset);
+            tokens[1].remaining = Some(20);
+            tokens[1].reset = Some(reset + 60);
+        }
+        let expected = GHRateLimit { remaining: 20, reset: Some(Utc.timestamp_opt(reset, 0).unwrap()) };
+        assert_eq!(Some(expected), client.rate_limit());
+    }
+
+    #[rstest]
+    #[case(StatusCode::Forbidden, &[("X-RateLimit-Remaining", "0")], true)]
+    #[case(StatusCode::Forbidden, &[("Retry-After", "60")], true)]
+    #[case(StatusCode::TooManyRequests, &[("Retry-After", "60")], true)]
+    #[case(StatusCode::Forbidden, &[("X-RateLimit-Remaining", "4999")], false)]
+    #[case(StatusCode::NotFound, &[("X-RateLimit-Remaining", "0")], false)]
+    fn test_error_from_response(#[case] status: StatusCode, #[case] headers: &[(&str, &str)], #[case] rate_limited: bool) {
+        let mut response = surf::http::Response::new(status);
+        for (name, value) in headers {
+            response.insert_header(*name, *value);
+        }
+        let error = GHError::from_response(&response.into());
+        assert_eq!(rate_limited, matches!(error, GHError::RateLimited(_)), "{error}");
+    }
+
+    #[rstest]
+    #[case("required; url=https://github.com/orgs/octo/sso?authorization_request=AZ", Some(GHSso::Required { url: "https://github.com/orgs/octo/sso?authorization_request=AZ".into() }))]
+    #[case("partial-results; organizations=21955855,20582480", Some(GHSso::PartialResults { organizations: vec!["21955855".into(), "20582480".into()] }))]
+    #[case("something-else", None)]
+    fn test_parse_sso_header(#[case] header: &str, #[case] expected: Option<GHSso>) {
+        assert_eq!(expected, GHSso::parse(header));
+    }
+
+    #[rstest]
+    #[case(Some(vec!["repo".into(), "read:org".into()]), None, 0)]
+    #[case(None, None, 0)]
+    #[case(Some(vec!["repo".into()]), None, 1)]
+    #[case(Some(vec!["repo".into()]), Some(GHSso::Required { url: "https://github.com/orgs/octo/sso".into() }), 2)]
+    fn test_org_access_warnings(#[case] scopes: Option<Vec<String>>, #[case] sso: Option<GHSso>, #[case] warnings: usize) {
+        let access = GHOrgAccess {
+            org: "octo".into(),
+            token: GHTokenInfo { login: "octocat".into(), scopes, expires_at: None },
+            sso,
+        };
+        assert_eq!(warnings, access.warnings().len());
+    }
+}
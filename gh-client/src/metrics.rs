@@ -0,0 +1,215 @@
+//! Request and sync metrics of a [crate::GHClient], rendered in the Prometheus text format.
+//!
+//! | metric                                | type      | labels             |
+//! |---------------------------------------|-----------|--------------------|
+//! | `gh_client_requests_total`            | counter   | `endpoint, status` |
+//! | `gh_client_request_duration_seconds`  | histogram | `endpoint`         |
+//! | `gh_client_retries_total`             | counter   |                    |
+//! | `gh_client_rate_limit_remaining`      | gauge     | `token` (index)    |
+//! | `gh_client_items_fetched_total`       | counter   | `kind`             |
+//! | `gh_sync_runs_total`                  | counter   | `org, result`      |
+//! | `gh_sync_duration_seconds`            | histogram | `org`              |
+//!
+//! Endpoints are URL paths with organization and user names replaced by `{org}` and `{user}`,
+//! requests failing without a response have the status `error`.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Upper bounds of the request duration buckets in seconds.
+const REQUEST_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+/// Upper bounds of the sync duration buckets in seconds.
+const SYNC_BUCKETS: &[f64] = &[1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// Number of observations per bucket, not cumulative.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self { bounds, counts: vec![0; bounds.len()], sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Data {
+    requests: BTreeMap<(String, String), u64>,
+    request_durations: BTreeMap<String, Histogram>,
+    retries: u64,
+    rate_limit_remaining: BTreeMap<usize, usize>,
+    items: BTreeMap<&'static str, u64>,
+    syncs: BTreeMap<(String, &'static str), u64>,
+    sync_durations: BTreeMap<String, Histogram>,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    data: Mutex<Data>,
+}
+
+/// Path of `url` with organization and user names replaced, to keep the number of label values
+/// bounded.
+pub fn endpoint(url: &str) -> String {
+    let path = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let path = path.find('/').map(|start| &path[start..]).unwrap_or("/");
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut previous = "";
+    path.split('/')
+        .map(|segment| {
+            let replaced = match previous {
+                "orgs" => "{org}",
+                "users" => "{user}",
+                _ => segment,
+            };
+            previous = segment;
+            replaced
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Metrics {
+    /// Record a request to `url`, `status` being `None` if no response was received.
+    pub fn record_request(&self, url: &str, status: Option<u16>, seconds: f64) {
+        let endpoint = endpoint(url);
+        let status = status.map(|status| status.to_string()).unwrap_or_else(|| "error".into());
+        let mut data = self.data.lock().unwrap();
+        *data.requests.entry((endpoint.clone(), status)).or_default() += 1;
+        data.request_durations.entry(endpoint).or_insert_with(|| Histogram::new(REQUEST_BUCKETS)).observe(seconds);
+    }
+
+    pub fn record_retry(&self) {
+        self.data.lock().unwrap().retries += 1;
+    }
+
+    /// Record the remaining rate-limit of the token with index `token`.
+    pub fn record_rate_limit(&self, token: usize, remaining: usize) {
+        self.data.lock().unwrap().rate_limit_remaining.insert(token, remaining);
+    }
+
+    /// Record `count` fetched items of `kind`, e.g. `members` or `repositories`.
+    pub fn record_items(&self, kind: &'static str, count: usize) {
+        *self.data.lock().unwrap().items.entry(kind).or_default() += count as u64;
+    }
+
+    /// Record a finished sync of `org`.
+    pub fn record_sync(&self, org: &str, success: bool, seconds: f64) {
+        let mut data = self.data.lock().unwrap();
+        let result = if success { "success" } else { "failure" };
+        *data.syncs.entry((org.to_string(), result)).or_default() += 1;
+        data.sync_durations.entry(org.to_string()).or_insert_with(|| Histogram::new(SYNC_BUCKETS)).observe(seconds);
+    }
+
+    /// All metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let data = self.data.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "gh_client_requests_total", "counter", "GH-API requests by endpoint and status.");
+        for ((endpoint, status), count) in &data.requests {
+            sample(&mut out, "gh_client_requests_total", &[("endpoint", endpoint), ("status", status)], *count as f64);
+        }
+        header(&mut out, "gh_client_request_duration_seconds", "histogram", "GH-API request latency by endpoint.");
+        for (endpoint, histogram) in &data.request_durations {
+            render_histogram(&mut out, "gh_client_request_duration_seconds", &[("endpoint", endpoint)], histogram);
+        }
+        header(&mut out, "gh_client_retries_total", "counter", "GH-API requests retried after a token hit its rate-limit.");
+        sample(&mut out, "gh_client_retries_total", &[], data.retries as f64);
+        header(&mut out, "gh_client_rate_limit_remaining", "gauge", "Remaining rate-limit by token index as of the last response.");
+        for (token, remaining) in &data.rate_limit_remaining {
+            sample(&mut out, "gh_client_rate_limit_remaining", &[("token", &token.to_string())], *remaining as f64);
+        }
+        header(&mut out, "gh_client_items_fetched_total", "counter", "Fetched members and repositories.");
+        for (kind, count) in &data.items {
+            sample(&mut out, "gh_client_items_fetched_total", &[("kind", kind)], *count as f64);
+        }
+        header(&mut out, "gh_sync_runs_total", "counter", "Finished syncs by organization and result.");
+        for ((org, result), count) in &data.syncs {
+            sample(&mut out, "gh_sync_runs_total", &[("org", org), ("result", result)], *count as f64);
+        }
+        header(&mut out, "gh_sync_duration_seconds", "histogram", "Duration of syncs by organization.");
+        for (org, histogram) in &data.sync_durations {
+            render_histogram(&mut out, "gh_sync_duration_seconds", &[("org", org)], histogram);
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} {kind}").unwrap();
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    if labels.is_empty() {
+        writeln!(out, "{name} {value}").unwrap();
+        return;
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| format!("{label}=\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+        .collect();
+    writeln!(out, "{name}{{{}}} {value}", labels.join(",")).unwrap();
+}
+
+fn render_histogram(out: &mut String, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+    let bucket = format!("{name}_bucket");
+    let mut cumulative = 0;
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+        cumulative += count;
+        let le = bound.to_string();
+        sample(out, &bucket, &[labels, &[("le", &le)]].concat(), cumulative as f64);
+    }
+    sample(out, &bucket, &[labels, &[("le", "+Inf")]].concat(), histogram.count as f64);
+    sample(out, &format!("{name}_sum"), labels, histogram.sum);
+    sample(out, &format!("{name}_count"), labels, histogram.count as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("https://api.github.com/orgs/octo/members?per_page=30&page=2", "/orgs/{org}/members")]
+    #[case("https://api.github.com/users/alice/repos", "/users/{user}/repos")]
+    #[case("https://api.github.com/user", "/user")]
+    #[case("https://api.github.com", "/")]
+    fn test_endpoint(#[case] url: &str, #[case] expected: &str) {
+        assert_eq!(expected, endpoint(url));
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.record_request("https://api.github.com/users/alice/repos", Some(200), 0.2);
+        metrics.record_request("https://api.github.com/users/bob/repos", Some(200), 3.0);
+        metrics.record_request("https://api.github.com/users/bob/repos", None, 0.01);
+        metrics.record_items("repositories", 30);
+        metrics.record_sync("octo", false, 2.0);
+
+        let text = metrics.render();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&r#"gh_client_requests_total{endpoint="/users/{user}/repos",status="200"} 2"#));
+        assert!(lines.contains(&r#"gh_client_requests_total{endpoint="/users/{user}/repos",status="error"} 1"#));
+        assert!(lines.contains(&r#"gh_client_request_duration_seconds_bucket{endpoint="/users/{user}/repos",le="0.25"} 2"#));
+        assert!(lines.contains(&r#"gh_client_request_duration_seconds_bucket{endpoint="/users/{user}/repos",le="+Inf"} 3"#));
+        assert!(lines.contains(&r#"gh_client_request_duration_seconds_count{endpoint="/users/{user}/repos"} 3"#));
+        assert!(lines.contains(&"gh_client_retries_total 0"));
+        assert!(lines.contains(&r#"gh_client_items_fetched_total{kind="repositories"} 30"#));
+        assert!(lines.contains(&r#"gh_sync_runs_total{org="octo",result="failure"} 1"#));
+    }
+}
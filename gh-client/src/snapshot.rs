@@ -0,0 +1,224 @@
+//! Versioned file format for the collected data of an organization.
+//!
+//! Snapshots are JSON objects with a `version` field. Reading a snapshot migrates older versions
+//! step by step to [SNAPSHOT_VERSION], so caches and files written by older clients stay usable.
+//!
+//! | version | content                                                              |
+//! |---------|----------------------------------------------------------------------|
+//! | 0       | bare `[[user, [repository, ...]], ...]` array without any metadata   |
+//! | 1       | `{version, org, fetched_at, client_version, data}`                   |
+//! | 2       | adds `etags`, repositories gain `stargazers_count` and `html_url`    |
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::crawl::CrawlFailure;
+use crate::{GHClient, GHConditional, GHRepository, GHUser};
+
+/// Version written by this client.
+pub const SNAPSHOT_VERSION: u64 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GHSnapshot {
+    pub version: u64,
+    /// Organization the data was collected for. Empty for migrated snapshots that did not record it.
+    pub org: String,
+    pub fetched_at: DateTime<Utc>,
+    /// Version of gh-client that collected the data.
+    pub client_version: String,
+    pub data: Vec<(GHUser, Vec<GHRepository>)>,
+    /// ETag of the repositories of each member login, to make the next refresh conditional.
+    #[serde(default)]
+    pub etags: HashMap<String, String>,
+}
+
+impl GHSnapshot {
+    /// Snapshot of data just fetched for `org`.
+    pub fn new(org: &str, data: Vec<(GHUser, Vec<GHRepository>)>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            org: org.to_string(),
+            fetched_at: Utc::now(),
+            client_version: env!("CARGO_PKG_VERSION").to_string(),
+            data,
+            etags: HashMap::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Whether the data is older than `ttl`.
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        Utc::now() - self.fetched_at > ttl
+    }
+
+    /// Read a snapshot of any known version, migrating it to the current version.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut value: Value = serde_json::from_str(json)?;
+        loop {
+            value = match version(&value)? {
+                0 => migrate_v0(value),
+                1 => migrate_v1(value),
+                SNAPSHOT_VERSION => return Ok(serde_json::from_value(value)?),
+                newer => return Err(anyhow!("Snapshot version {newer} was written by a newer client, expected at most {SNAPSHOT_VERSION}")),
+            };
+        }
+    }
+}
+
+impl GHClient {
+    /// Fetch a new snapshot of the organization of `snapshot`, reusing its data where possible.
+    ///
+    /// The repositories of cached members are requested conditionally with the ETag of the
+    /// previous refresh, so members whose repositories did not change, neither by pushes nor by
+    /// stars, usually cost a `304 Not Modified`.
+    ///
+    /// Members whose fetch fails keep their previous repositories, new members without any are
+    /// left out. They are returned along with the snapshot, which then keeps the previous fetch
+    /// time so that it still expires as before.
+    #[tracing::instrument(skip_all, fields(org = %snapshot.org))]
+    pub async fn refresh_snapshot(&self, snapshot: &GHSnapshot) -> Result<(GHSnapshot, Vec<CrawlFailure>)> {
+        let cached: HashMap<&str, &Vec<GHRepository>> = snapshot.data.iter().map(|(user, repos)| (user.login.as_str(), repos)).collect();
+        let members = self.get_org_members(&snapshot.org).await?;
+
+        let updates = ::futures::future::join_all(members.into_iter().map(|user| {
+            let cached = cached.get(user.login.as_str()).copied();
+            // without cached repositories a `304 Not Modified` would leave nothing to show
+            let etag = cached.and(snapshot.etags.get(&user.login)).cloned();
+            async move {
+                match self.get_user_repositories_if_modified(&user.login, etag.as_deref()).await {
+                    Ok(GHConditional::Modified { value, etag }) => Ok((user, Some(value), etag)),
+                    Ok(GHConditional::NotModified) => Ok((user, cached.cloned(), etag)),
+                    Err(msg) => {
+                        tracing::warn!("Failed to fetch repos for {}: {msg}", user.login);
+                        Err((CrawlFailure::new(&user.login, &msg), user, cached.cloned(), etag))
+                    }
+                }
+            }
+        }))
+        .await;
+
+        let mut refreshed = GHSnapshot::new(&snapshot.org, Vec::new());
+        let mut failures = Vec::new();
+        for update in updates {
+            let (user, repos, etag) = match update {
+                Ok(update) => update,
+                Err((failure, user, repos, etag)) => {
+                    failures.push(failure);
+                    (user, repos, etag)
+                }
+            };
+            let Some(repos) = repos else { continue };
+            if let Some(etag) = etag {
+                refreshed.etags.insert(user.login.clone(), etag);
+            }
+            refreshed.data.push((user, repos));
+        }
+        if !failures.is_empty() {
+            refreshed.fetched_at = snapshot.fetched_at;
+        }
+        Ok((refreshed, failures))
+    }
+}
+
+fn version(value: &Value) -> Result<u64> {
+    match value {
+        Value::Array(_) => Ok(0),
+        Value::Object(object) => object
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| anyhow!("Snapshot lacks a version")),
+        _ => Err(anyhow!("Snapshot is neither an object nor an array")),
+    }
+}
+
+/// Wrap the bare data array into a version 1 object. The unknown fetch time is set to the unix
+/// epoch, so the data counts as outdated.
+fn migrate_v0(data: Value) -> Value {
+    json!({
+        "version": 1,
+        "org": "",
+        "fetched_at": Utc.timestamp_opt(0, 0).unwrap(),
+        "client_version": "unknown",
+        "data": data,
+    })
+}
+
+/// Add the ETags of version 2. Repositories of version 1 lack stars and links, so the fetch time
+/// is set to the unix epoch and the data counts as outdated, and without ETags the next refresh
+/// fetches all repositories again.
+fn migrate_v1(mut snapshot: Value) -> Value {
+    snapshot["version"] = json!(2);
+    snapshot["fetched_at"] = json!(Utc.timestamp_opt(0, 0).unwrap());
+    snapshot["etags"] = json!({});
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{repo, user};
+
+    fn data() -> Vec<(GHUser, Vec<GHRepository>)> {
+        vec![(user("alice", 1), vec![repo("a", Some("Rust"))])]
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut snapshot = GHSnapshot::new("octo", data());
+        snapshot.etags.insert("alice".into(), "\"abc\"".into());
+        let snapshot = GHSnapshot::from_json(&snapshot.to_json()?)?;
+        assert_eq!(SNAPSHOT_VERSION, snapshot.version);
+        assert_eq!("octo", snapshot.org);
+        assert_eq!(1, snapshot.data.len());
+        assert_eq!(Some("\"abc\""), snapshot.etags.get("alice").map(String::as_str));
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_v0() -> Result<()> {
+        let snapshot = GHSnapshot::from_json(&serde_json::to_string(&data())?)?;
+        assert_eq!(SNAPSHOT_VERSION, snapshot.version);
+        assert_eq!("", snapshot.org);
+        assert_eq!(0, snapshot.fetched_at.timestamp());
+        assert_eq!("alice", snapshot.data[0].0.login);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_v1() -> Result<()> {
+        let json = json!({
+            "version": 1,
+            "org": "octo",
+            "fetched_at": Utc::now(),
+            "client_version": "0.1.0",
+            "data": [[data()[0].0, [{"name": "a", "language": "Rust"}]]],
+        });
+        let snapshot = GHSnapshot::from_json(&json.to_string())?;
+        assert_eq!(SNAPSHOT_VERSION, snapshot.version);
+        assert_eq!("octo", snapshot.org);
+        assert_eq!(0, snapshot.fetched_at.timestamp());
+        assert!(snapshot.etags.is_empty());
+        assert_eq!(0, snapshot.data[0].1[0].stargazers_count);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let mut snapshot = GHSnapshot::new("octo", data());
+        assert!(!snapshot.is_expired(Duration::hours(1)));
+        snapshot.fetched_at = Utc::now() - Duration::hours(2);
+        assert!(snapshot.is_expired(Duration::hours(1)));
+    }
+
+    #[test]
+    fn test_reject_newer_version() {
+        let json = json!({"version": SNAPSHOT_VERSION + 1, "data": []}).to_string();
+        assert!(GHSnapshot::from_json(&json).is_err());
+    }
+}
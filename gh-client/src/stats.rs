@@ -0,0 +1,90 @@
+//! Aggregations over the `(user, repositories)` pairs of an organization.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{language_count, GHRepository, GHUser};
+
+/// Number of repositories and users of a language within an organization.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LanguageTotal {
+    pub language: String,
+    /// Number of repositories written in the language.
+    pub repositories: usize,
+    /// Number of users owning at least one repository in the language.
+    pub users: usize,
+}
+
+/// A user together with the number of their repositories in some language.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LanguageUser {
+    pub login: String,
+    pub repositories: usize,
+}
+
+/// Repository and user counts for every language, most used languages first.
+pub fn language_totals(user_repos: &[(GHUser, Vec<GHRepository>)]) -> Vec<LanguageTotal> {
+    let mut totals: HashMap<String, LanguageTotal> = HashMap::new();
+    for (_, repos) in user_repos {
+        for (language, count) in language_count(repos) {
+            let total = totals.entry(language.clone()).or_insert(LanguageTotal { language, repositories: 0, users: 0 });
+            total.repositories += count;
+            total.users += 1;
+        }
+    }
+    let mut totals: Vec<LanguageTotal> = totals.into_values().collect();
+    totals.sort_by(|a, b| b.repositories.cmp(&a.repositories).then_with(|| a.language.cmp(&b.language)));
+    totals
+}
+
+/// Users with repositories in `language` (case-insensitive), most repositories first.
+pub fn users_by_language(user_repos: &[(GHUser, Vec<GHRepository>)], language: &str) -> Vec<LanguageUser> {
+    let mut users: Vec<LanguageUser> = user_repos
+        .iter()
+        .filter_map(|(user, repos)| {
+            let repositories = repos
+                .iter()
+                .filter(|repo| repo.language.as_deref().map(|l| l.eq_ignore_ascii_case(language)).unwrap_or(false))
+                .count();
+            (repositories > 0).then(|| LanguageUser { login: user.login.clone(), repositories })
+        })
+        .collect();
+    users.sort_by(|a, b| b.repositories.cmp(&a.repositories).then_with(|| a.login.cmp(&b.login)));
+    users
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{repo, user};
+
+    fn user_repos() -> Vec<(GHUser, Vec<GHRepository>)> {
+        vec![
+            (user("alice", 1), vec![repo("a", Some("Rust")), repo("b", Some("Rust")), repo("c", None)]),
+            (user("bob", 2), vec![repo("d", Some("Python")), repo("e", Some("Rust"))]),
+        ]
+    }
+
+    #[test]
+    fn test_language_totals() {
+        assert_eq!(
+            vec![
+                LanguageTotal { language: "Rust".into(), repositories: 3, users: 2 },
+                LanguageTotal { language: "Python".into(), repositories: 1, users: 1 },
+            ],
+            language_totals(&user_repos())
+        );
+    }
+
+    #[test]
+    fn test_users_by_language() {
+        assert_eq!(
+            vec![
+                LanguageUser { login: "alice".into(), repositories: 2 },
+                LanguageUser { login: "bob".into(), repositories: 1 },
+            ],
+            users_by_language(&user_repos(), "rust")
+        );
+        assert!(users_by_language(&user_repos(), "go").is_empty());
+    }
+}
@@ -0,0 +1,488 @@
+//! SQLite persistence of synced organization data.
+//!
+//! Users and their repositories are stored once, organizations reference their members. Every
+//! sync is recorded as a run with its outcome. The schema is created and upgraded on
+//! [Storage::open] using the `user_version` pragma.
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::crawl::{failure_summary, CrawlFailure};
+use crate::stats::{LanguageTotal, LanguageUser};
+use crate::{GHClient, GHRepository, GHUser};
+
+/// Schema migrations, `MIGRATIONS[i]` upgrades from `user_version` i to i + 1.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE users (
+        id INTEGER PRIMARY KEY,
+        login TEXT NOT NULL,
+        avatar_url TEXT NOT NULL,
+        repos_url TEXT NOT NULL
+    );
+    CREATE UNIQUE INDEX users_login ON users (login);
+    CREATE TABLE org_members (
+        org TEXT NOT NULL,
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        PRIMARY KEY (org, user_id)
+    );
+    CREATE TABLE languages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE repositories (
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        language_id INTEGER REFERENCES languages (id),
+        PRIMARY KEY (user_id, name)
+    );
+    CREATE TABLE sync_runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        org TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        finished_at TEXT,
+        status TEXT NOT NULL,
+        error TEXT
+    );
+"#, r#"
+    ALTER TABLE repositories ADD COLUMN pushed_at TEXT;
+    CREATE TABLE sync_checkpoints (
+        user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+        etag TEXT,
+        pushed_at TEXT,
+        fetched_at TEXT NOT NULL
+    );
+"#, r#"
+    ALTER TABLE repositories ADD COLUMN stargazers_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE repositories ADD COLUMN html_url TEXT NOT NULL DEFAULT '';
+"#];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStatus {
+    Running,
+    Success,
+    Failure,
+}
+
+impl SyncStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SyncStatus::Running => "running",
+            SyncStatus::Success => "success",
+            SyncStatus::Failure => "failure",
+        }
+    }
+
+    fn parse(status: &str) -> Result<Self> {
+        match status {
+            "running" => Ok(SyncStatus::Running),
+            "success" => Ok(SyncStatus::Success),
+            "failure" => Ok(SyncStatus::Failure),
+            _ => Err(anyhow!("Unknown sync status {status}")),
+        }
+    }
+}
+
+/// A recorded sync of an organization.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SyncRun {
+    pub id: i64,
+    pub org: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: SyncStatus,
+    pub error: Option<String>,
+}
+
+/// Incremental sync state of a user.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SyncCheckpoint {
+    /// ETag of the latest-push request, to make the next one conditional.
+    pub etag: Option<String>,
+    /// Latest push over all repositories of the user when they were last fetched.
+    pub pushed_at: Option<DateTime<Utc>>,
+    /// When the repositories of the user were last fetched completely.
+    pub fetched_at: DateTime<Utc>,
+}
+
+pub struct Storage {
+    connection: Connection,
+}
+
+impl Storage {
+    /// Open (or create) the database at `path` and bring its schema up to date.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::migrated(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::migrated(Connection::open_in_memory()?)
+    }
+
+    fn migrated(connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let mut storage = Self { connection };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let version: usize = self.connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(anyhow!("Database schema version {version} is newer than supported version {}", MIGRATIONS.len()));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            tracing::info!("migrating database schema to version {}", index + 1);
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Record the start of a sync of `org`, returning the id of the run.
+    pub fn begin_sync(&self, org: &str) -> Result<i64> {
+        self.connection.execute(
+            "INSERT INTO sync_runs (org, started_at, status) VALUES (?1, ?2, ?3)",
+            params![org, Utc::now(), SyncStatus::Running.as_str()],
+        )?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// Record the outcome of a sync run, a failure if `error` is given.
+    pub fn finish_sync(&self, run: i64, error: Option<&str>) -> Result<()> {
+        let status = if error.is_some() { SyncStatus::Failure } else { SyncStatus::Success };
+        self.connection.execute(
+            "UPDATE sync_runs SET finished_at = ?2, status = ?3, error = ?4 WHERE id = ?1",
+            params![run, Utc::now(), status.as_str(), error],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent sync run of `org`, if any.
+    pub fn last_sync(&self, org: &str) -> Result<Option<SyncRun>> {
+        let run = self
+            .connection
+            .query_row(
+                "SELECT id, org, started_at, finished_at, status, error FROM sync_runs WHERE org = ?1 ORDER BY id DESC LIMIT 1",
+                params![org],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get::<_, String>(4)?, row.get(5)?)),
+            )
+            .optional()?;
+        run.map(|(id, org, started_at, finished_at, status, error)| {
+            Ok(SyncRun { id, org, started_at, finished_at, status: SyncStatus::parse(&status)?, error })
+        })
+        .transpose()
+    }
+
+    /// Logins of the stored members of `org`.
+    pub fn member_logins(&self, org: &str) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare(
+            "SELECT u.login FROM users u JOIN org_members m ON m.user_id = u.id WHERE m.org = ?1 ORDER BY u.login",
+        )?;
+        let logins = statement.query_map(params![org], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(logins)
+    }
+
+    /// The incremental sync state of the user with `login`, `None` if never synced.
+    pub fn checkpoint(&self, login: &str) -> Result<Option<SyncCheckpoint>> {
+        let checkpoint = self
+            .connection
+            .query_row(
+                "SELECT c.etag, c.pushed_at, c.fetched_at FROM sync_checkpoints c JOIN users u ON u.id = c.user_id WHERE u.login = ?1",
+                params![login],
+                |row| Ok(SyncCheckpoint { etag: row.get(0)?, pushed_at: row.get(1)?, fetched_at: row.get(2)? }),
+            )
+            .optional()?;
+        Ok(checkpoint)
+    }
+
+    /// Store the incremental sync state of the user with `login`, who has to be stored already.
+    pub fn set_checkpoint(&self, login: &str, checkpoint: &SyncCheckpoint) -> Result<()> {
+        let updated = self.connection.execute(
+            "INSERT INTO sync_checkpoints (user_id, etag, pushed_at, fetched_at)
+             SELECT id, ?2, ?3, ?4 FROM users WHERE login = ?1
+             ON CONFLICT (user_id) DO UPDATE SET etag = excluded.etag, pushed_at = excluded.pushed_at, fetched_at = excluded.fetched_at",
+            params![login, checkpoint.etag, checkpoint.pushed_at, checkpoint.fetched_at],
+        )?;
+        if updated == 0 {
+            return Err(anyhow!("Unknown user {login}"));
+        }
+        Ok(())
+    }
+
+    /// Replace the members of `org` with `users`, inserting or updating the users themselves.
+    pub fn upsert_members(&mut self, org: &str, users: &[GHUser]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM org_members WHERE org = ?1", params![org])?;
+        for user in users {
+            transaction.execute(
+                "INSERT INTO users (id, login, avatar_url, repos_url) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (id) DO UPDATE SET login = excluded.login, avatar_url = excluded.avatar_url, repos_url = excluded.repos_url",
+                params![user.id as i64, user.login, user.avatar_url, user.repos_url],
+            )?;
+            transaction.execute("INSERT INTO org_members (org, user_id) VALUES (?1, ?2)", params![org, user.id as i64])?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Replace the repositories of the user with `login`, who has to be stored already.
+    pub fn upsert_repositories(&mut self, login: &str, repos: &[GHRepository]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        let user_id: i64 = transaction
+            .query_row("SELECT id FROM users WHERE login = ?1", params![login], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| anyhow!("Unknown user {login}"))?;
+        transaction.execute("DELETE FROM repositories WHERE user_id = ?1", params![user_id])?;
+        for repo in repos {
+            let language_id: Option<i64> = match &repo.language {
+                Some(language) => {
+                    transaction.execute("INSERT OR IGNORE INTO languages (name) VALUES (?1)", params![language])?;
+                    Some(transaction.query_row("SELECT id FROM languages WHERE name = ?1", params![language], |row| row.get(0))?)
+                }
+                None => None,
+            };
+            transaction.execute(
+                "INSERT INTO repositories (user_id, name, language_id, pushed_at, stargazers_count, html_url)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![user_id, repo.name, language_id, repo.pushed_at, repo.stargazers_count as i64, repo.html_url],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Store the members of `org` together with their repositories.
+    pub fn store_org(&mut self, org: &str, user_repos: &[(GHUser, Vec<GHRepository>)]) -> Result<()> {
+        let users: Vec<GHUser> = user_repos.iter().map(|(user, _)| user.clone()).collect();
+        self.upsert_members(org, &users)?;
+        for (user, repos) in user_repos {
+            self.upsert_repositories(&user.login, repos)?;
+        }
+        Ok(())
+    }
+
+    /// Load the members of `org` together with their repositories, ordered by login.
+    pub fn org_data(&self, org: &str) -> Result<Vec<(GHUser, Vec<GHRepository>)>> {
+        let mut users = self.connection.prepare(
+            "SELECT u.id, u.login, u.avatar_url, u.repos_url FROM users u
+             JOIN org_members m ON m.user_id = u.id WHERE m.org = ?1 ORDER BY u.login",
+        )?;
+        let mut repos = self.connection.prepare(
+            "SELECT r.name, l.name, r.pushed_at, r.stargazers_count, r.html_url FROM repositories r
+             LEFT JOIN languages l ON l.id = r.language_id
+             WHERE r.user_id = ?1 ORDER BY r.name",
+        )?;
+        let users = users
+            .query_map(params![org], |row| {
+                Ok(GHUser { id: row.get::<_, i64>(0)? as usize, login: row.get(1)?, avatar_url: row.get(2)?, repos_url: row.get(3)? })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        users
+            .into_iter()
+            .map(|user| {
+                let user_repos = repos
+                    .query_map(params![user.id as i64], |row| {
+                        Ok(GHRepository {
+                            name: row.get(0)?,
+                            language: row.get(1)?,
+                            pushed_at: row.get(2)?,
+                            stargazers_count: row.get::<_, i64>(3)? as usize,
+                            html_url: row.get(4)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((user, user_repos))
+            })
+            .collect()
+    }
+
+    /// Members of `org` with repositories in `language` (case-insensitive), most repositories first.
+    pub fn users_by_language(&self, org: &str, language: &str) -> Result<Vec<LanguageUser>> {
+        let mut statement = self.connection.prepare(
+            "SELECT u.login, COUNT(*) FROM repositories r
+             JOIN users u ON u.id = r.user_id
+             JOIN languages l ON l.id = r.language_id
+             JOIN org_members m ON m.user_id = u.id
+             WHERE m.org = ?1 AND l.name = ?2 COLLATE NOCASE
+             GROUP BY u.id ORDER BY COUNT(*) DESC, u.login",
+        )?;
+        let users = statement
+            .query_map(params![org, language], |row| {
+                Ok(LanguageUser { login: row.get(0)?, repositories: row.get::<_, i64>(1)? as usize })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(users)
+    }
+
+    /// Repository and user counts for every language of `org`, most used languages first.
+    pub fn language_totals(&self, org: &str) -> Result<Vec<LanguageTotal>> {
+        let mut statement = self.connection.prepare(
+            "SELECT l.name, COUNT(*), COUNT(DISTINCT r.user_id) FROM repositories r
+             JOIN languages l ON l.id = r.language_id
+             JOIN org_members m ON m.user_id = r.user_id
+             WHERE m.org = ?1
+             GROUP BY l.id ORDER BY COUNT(*) DESC, l.name",
+        )?;
+        let totals = statement
+            .query_map(params![org], |row| {
+                Ok(LanguageTotal {
+                    language: row.get(0)?,
+                    repositories: row.get::<_, i64>(1)? as usize,
+                    users: row.get::<_, i64>(2)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(totals)
+    }
+}
+
+/// Fetch the members of `org` and their repositories and store them, recording the sync run.
+///
+/// Members whose repositories cannot be fetched keep their previously stored repositories. They
+/// are returned and fail the recorded sync run.
+#[tracing::instrument(skip(client, storage))]
+pub async fn sync_org(client: &GHClient, storage: &mut Storage, org: &str) -> Result<Vec<CrawlFailure>> {
+    let run = storage.begin_sync(org)?;
+    let start = Utc::now();
+    let result: Result<Vec<CrawlFailure>> = async {
+        let users = client.get_org_members(org).await?;
+        storage.upsert_members(org, &users)?;
+        let user_repos = ::futures::future::join_all(users.iter().map(|user| async move {
+            (user, client.get_user_repositories(&user.login).await)
+        }))
+        .await;
+        let mut failures = Vec::new();
+        for (user, repos) in user_repos {
+            match repos {
+                Ok(repos) => {
+                    storage.upsert_repositories(&user.login, &repos)?;
+                    // lets the next incremental sync skip the user if nothing was pushed since
+                    let pushed_at = repos.iter().filter_map(|repo| repo.pushed_at).max();
+                    storage.set_checkpoint(&user.login, &SyncCheckpoint { etag: None, pushed_at, fetched_at: Utc::now() })?;
+                }
+                Err(msg) => {
+                    tracing::warn!("Failed to fetch repos for {}: {msg}", user.login);
+                    failures.push(CrawlFailure::new(&user.login, &msg));
+                }
+            }
+        }
+        Ok(failures)
+    }
+    .await;
+    let error = match &result {
+        Ok(failures) if failures.is_empty() => None,
+        Ok(failures) => Some(failure_summary(failures)),
+        Err(msg) => Some(msg.to_string()),
+    };
+    storage.finish_sync(run, error.as_deref())?;
+    client.metrics().record_sync(org, error.is_none(), (Utc::now() - start).num_milliseconds() as f64 / 1000.0);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{repo, user};
+    use chrono::TimeZone;
+
+    fn storage() -> Result<Storage> {
+        let mut storage = Storage::open_in_memory()?;
+        storage.store_org(
+            "octo",
+            &[
+                (user("alice", 1), vec![repo("a", Some("Rust")), repo("b", Some("Rust")), repo("c", None)]),
+                (user("bob", 2), vec![repo("d", Some("Python")), repo("e", Some("Rust"))]),
+            ],
+        )?;
+        Ok(storage)
+    }
+
+    #[test]
+    fn test_queries() -> Result<()> {
+        let storage = storage()?;
+        assert_eq!(
+            vec![
+                LanguageUser { login: "alice".into(), repositories: 2 },
+                LanguageUser { login: "bob".into(), repositories: 1 },
+            ],
+            storage.users_by_language("octo", "rust")?
+        );
+        assert_eq!(
+            vec![
+                LanguageTotal { language: "Rust".into(), repositories: 3, users: 2 },
+                LanguageTotal { language: "Python".into(), repositories: 1, users: 1 },
+            ],
+            storage.language_totals("octo")?
+        );
+        assert!(storage.language_totals("other")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_upsert_replaces() -> Result<()> {
+        let mut storage = storage()?;
+        // bob left the org, alice renamed herself and deleted a repository
+        storage.upsert_members("octo", &[user("alicia", 1)])?;
+        storage.upsert_repositories("alicia", &[repo("a", Some("Rust"))])?;
+
+        let data = storage.org_data("octo")?;
+        assert_eq!(1, data.len());
+        assert_eq!("alicia", data[0].0.login);
+        assert_eq!(1, data[0].1.len());
+        assert!(storage.users_by_language("octo", "python")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repository_round_trip() -> Result<()> {
+        let mut storage = storage()?;
+        let starred = GHRepository {
+            pushed_at: Utc.timestamp_opt(1_700_000_000, 0).single(),
+            stargazers_count: 7,
+            html_url: "https://github.com/alice/a".into(),
+            ..repo("a", Some("Rust"))
+        };
+        storage.upsert_repositories("alice", std::slice::from_ref(&starred))?;
+        assert_eq!(vec![starred], storage.org_data("octo")?[0].1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_stars() -> Result<()> {
+        // a database written before repositories had stars and links
+        let connection = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..2] {
+            connection.execute_batch(migration)?;
+        }
+        connection.pragma_update(None, "user_version", 2)?;
+        connection.execute_batch(
+            "INSERT INTO users (id, login, avatar_url, repos_url) VALUES (1, 'alice', '', '');
+             INSERT INTO org_members (org, user_id) VALUES ('octo', 1);
+             INSERT INTO repositories (user_id, name) VALUES (1, 'a');",
+        )?;
+
+        let storage = Storage::migrated(connection)?;
+        assert_eq!(vec![repo("a", None)], storage.org_data("octo")?[0].1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_runs() -> Result<()> {
+        let storage = Storage::open_in_memory()?;
+        assert_eq!(None, storage.last_sync("octo")?);
+        let run = storage.begin_sync("octo")?;
+        assert_eq!(SyncStatus::Running, storage.last_sync("octo")?.unwrap().status);
+        storage.finish_sync(run, Some("rate limited"))?;
+        let last = storage.last_sync("octo")?.unwrap();
+        assert_eq!(SyncStatus::Failure, last.status);
+        assert_eq!(Some("rate limited".to_string()), last.error);
+        assert!(last.finished_at.is_some());
+        Ok(())
+    }
+}
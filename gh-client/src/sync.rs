@@ -0,0 +1,204 @@
+//! Incremental sync of an organization into [Storage].
+//!
+//! Instead of downloading the repositories of every member on every sync, each member is first
+//! checked with a conditional request for their most recently pushed repository. Only members
+//! that pushed since their [SyncCheckpoint], or whose last complete fetch is older than a
+//! cutoff, get their repositories fetched again. Unchanged members cost a single request that
+//! usually answers `304 Not Modified` and does not count against the rate-limit.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::crawl::{failure_summary, CrawlFailure};
+use crate::storage::{Storage, SyncCheckpoint};
+use crate::{GHClient, GHConditional, GHRepository, GHUser};
+
+/// Outcome of an incremental sync.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub members: usize,
+    pub joined: Vec<String>,
+    pub left: Vec<String>,
+    /// Members whose repositories were fetched completely.
+    pub refetched: Vec<String>,
+    /// Number of members skipped because nothing changed since the last sync.
+    pub unchanged: usize,
+    /// Members whose check or fetch failed. They keep their previously stored repositories.
+    pub failed: Vec<CrawlFailure>,
+}
+
+enum MemberUpdate {
+    /// Repositories are unchanged, possibly with a new checkpoint to store.
+    Unchanged(Option<SyncCheckpoint>),
+    Refetched(Vec<GHRepository>, SyncCheckpoint),
+}
+
+/// Whether the repositories of a member have to be fetched again.
+fn needs_refetch(
+    checkpoint: Option<&SyncCheckpoint>,
+    latest: &GHConditional<Option<DateTime<Utc>>>,
+    now: DateTime<Utc>,
+    max_age: Duration,
+) -> bool {
+    match (checkpoint, latest) {
+        (None, _) => true,
+        (Some(checkpoint), _) if now - checkpoint.fetched_at > max_age => true,
+        (Some(_), GHConditional::NotModified) => false,
+        // an earlier latest push than before means the latest repository was deleted
+        (Some(checkpoint), GHConditional::Modified { value, .. }) => *value != checkpoint.pushed_at,
+    }
+}
+
+#[tracing::instrument(skip_all, fields(login = %user.login))]
+async fn update_member(
+    client: &GHClient,
+    user: &GHUser,
+    checkpoint: Option<SyncCheckpoint>,
+    now: DateTime<Utc>,
+    max_age: Duration,
+) -> Result<MemberUpdate> {
+    let latest = client
+        .get_latest_push(&user.login, checkpoint.as_ref().and_then(|checkpoint| checkpoint.etag.as_deref()))
+        .await?;
+    let etag = match &latest {
+        GHConditional::Modified { etag, .. } => etag.clone(),
+        GHConditional::NotModified => checkpoint.as_ref().and_then(|checkpoint| checkpoint.etag.clone()),
+    };
+
+    if !needs_refetch(checkpoint.as_ref(), &latest, now, max_age) {
+        tracing::debug!("{} did not push since the last sync", user.login);
+        return Ok(MemberUpdate::Unchanged(checkpoint.map(|checkpoint| SyncCheckpoint { etag, ..checkpoint })));
+    }
+
+    let repos = client.get_user_repositories(&user.login).await?;
+    let pushed_at = repos.iter().filter_map(|repo| repo.pushed_at).max();
+    Ok(MemberUpdate::Refetched(repos, SyncCheckpoint { etag, pushed_at, fetched_at: now }))
+}
+
+/// Sync `org` incrementally, recording the sync run.
+///
+/// Members joining the org are fetched completely, members leaving it are removed from the org.
+/// Members whose repositories were fetched longer than `max_age` ago are fetched completely to
+/// catch changes that do not show up in the latest push, like deleted repositories.
+///
+/// Failed members fail the recorded sync run.
+#[tracing::instrument(skip(client, storage))]
+pub async fn sync_org_incremental(client: &GHClient, storage: &mut Storage, org: &str, max_age: Duration) -> Result<SyncReport> {
+    let run = storage.begin_sync(org)?;
+    let start = Utc::now();
+    let result = sync(client, storage, org, max_age).await;
+    let error = match &result {
+        Ok(report) if report.failed.is_empty() => None,
+        Ok(report) => Some(failure_summary(&report.failed)),
+        Err(msg) => Some(msg.to_string()),
+    };
+    storage.finish_sync(run, error.as_deref())?;
+    client.metrics().record_sync(org, error.is_none(), (Utc::now() - start).num_milliseconds() as f64 / 1000.0);
+    result
+}
+
+async fn sync(client: &GHClient, storage: &mut Storage, org: &str, max_age: Duration) -> Result<SyncReport> {
+    let previous: HashSet<String> = storage.member_logins(org)?.into_iter().collect();
+    let users = client.get_org_members(org).await?;
+    let current: HashSet<&str> = users.iter().map(|user| user.login.as_str()).collect();
+
+    let mut report = SyncReport {
+        members: users.len(),
+        joined: users.iter().filter(|user| !previous.contains(&user.login)).map(|user| user.login.clone()).collect(),
+        left: previous.iter().filter(|login| !current.contains(login.as_str())).cloned().collect(),
+        ..Default::default()
+    };
+    report.left.sort();
+    storage.upsert_members(org, &users)?;
+
+    let checkpoints: HashMap<&str, Option<SyncCheckpoint>> = users
+        .iter()
+        .map(|user| Ok((user.login.as_str(), storage.checkpoint(&user.login)?)))
+        .collect::<Result<_>>()?;
+    let now = Utc::now();
+    let updates = ::futures::future::join_all(users.iter().map(|user| {
+        let checkpoint = checkpoints[user.login.as_str()].clone();
+        async move { (user, update_member(client, user, checkpoint, now, max_age).await) }
+    }))
+    .await;
+
+    for (user, update) in updates {
+        match update {
+            Ok(MemberUpdate::Unchanged(checkpoint)) => {
+                if let Some(checkpoint) = checkpoint {
+                    storage.set_checkpoint(&user.login, &checkpoint)?;
+                }
+                report.unchanged += 1;
+            }
+            Ok(MemberUpdate::Refetched(repos, checkpoint)) => {
+                storage.upsert_repositories(&user.login, &repos)?;
+                storage.set_checkpoint(&user.login, &checkpoint)?;
+                report.refetched.push(user.login.clone());
+            }
+            Err(msg) => {
+                tracing::warn!("Failed to sync repos of {}: {msg}", user.login);
+                report.failed.push(CrawlFailure::new(&user.login, &msg));
+            }
+        }
+    }
+    tracing::info!(
+        "synced {org}: {} members, {} joined, {} left, {} refetched, {} unchanged, {} failed",
+        report.members,
+        report.joined.len(),
+        report.left.len(),
+        report.refetched.len(),
+        report.unchanged,
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::user;
+    use chrono::TimeZone;
+
+    fn time(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2022, 12, day, 0, 0, 0).unwrap()
+    }
+
+    fn checkpoint(pushed_at: u32, fetched_at: u32) -> SyncCheckpoint {
+        SyncCheckpoint { etag: Some("\"abc\"".into()), pushed_at: Some(time(pushed_at)), fetched_at: time(fetched_at) }
+    }
+
+    fn modified(pushed_at: u32) -> GHConditional<Option<DateTime<Utc>>> {
+        GHConditional::Modified { value: Some(time(pushed_at)), etag: Some("\"def\"".into()) }
+    }
+
+    #[test]
+    fn test_needs_refetch() {
+        let max_age = Duration::days(7);
+        let now = time(10);
+        // never synced
+        assert!(needs_refetch(None, &GHConditional::NotModified, now, max_age));
+        // nothing changed
+        assert!(!needs_refetch(Some(&checkpoint(2, 5)), &GHConditional::NotModified, now, max_age));
+        // changed metadata, but no push
+        assert!(!needs_refetch(Some(&checkpoint(2, 5)), &modified(2), now, max_age));
+        // pushed since
+        assert!(needs_refetch(Some(&checkpoint(2, 5)), &modified(8), now, max_age));
+        // latest repository deleted
+        assert!(needs_refetch(Some(&checkpoint(2, 5)), &modified(1), now, max_age));
+        // outdated
+        assert!(needs_refetch(Some(&checkpoint(2, 2)), &GHConditional::NotModified, now, max_age));
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() -> Result<()> {
+        let mut storage = Storage::open_in_memory()?;
+        storage.upsert_members("octo", &[user("alice", 1)])?;
+        assert_eq!(None, storage.checkpoint("alice")?);
+        storage.set_checkpoint("alice", &checkpoint(2, 5))?;
+        assert_eq!(Some(checkpoint(2, 5)), storage.checkpoint("alice")?);
+        assert!(storage.set_checkpoint("bob", &checkpoint(2, 5)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! Inline SVG charts of language distributions.
+//!
+//! Every bar or segment carries a `data-language` attribute, so a single click listener on a
+//! surrounding element can filter by the clicked language, and a `<title>` shown as tooltip.
+use web_sys::{Document, Element};
+
+use super::{append_node, set_attribute, Component};
+
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+
+/// A language with its value in a chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChartEntry {
+    pub language: String,
+    pub value: usize,
+    pub tooltip: String,
+}
+
+fn create_svg(document: &Document, tag: &str) -> Element {
+    document.create_element_ns(Some(SVG_NS), tag).expect("invalid tag")
+}
+
+fn svg(document: &Document, parent: &Element, tag: &str, attributes: &[(&str, &str)]) -> Element {
+    let element = create_svg(document, tag);
+    for (name, value) in attributes {
+        set_attribute(&element, name, value);
+    }
+    append_node(parent, &element);
+    element
+}
+
+/// A clickable group of an entry, with its tooltip.
+fn entry_group(document: &Document, parent: &Element, entry: &ChartEntry) -> Element {
+    let group = svg(document, parent, "g", &[("data-language", &entry.language), ("style", "cursor: pointer;")]);
+    svg(document, &group, "title", &[]).set_text_content(Some(&entry.tooltip));
+    group
+}
+
+/// Stable color of a language, so it looks the same in every chart.
+pub fn language_color(language: &str) -> String {
+    let hash = language.bytes().fold(7u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    format!("hsl({}, 60%, 55%)", hash % 360)
+}
+
+/// Horizontal bars, one row per entry in the given order.
+pub struct BarChart {
+    chart: Element,
+    entries: Vec<ChartEntry>,
+}
+
+impl BarChart {
+    const ROW_HEIGHT: usize = 20;
+    const LABEL_WIDTH: usize = 120;
+    const BAR_WIDTH: usize = 320;
+
+    fn render(&self, document: &Document) {
+        self.chart.set_inner_html("");
+        let height = (self.entries.len() * Self::ROW_HEIGHT).max(Self::ROW_HEIGHT);
+        let width = Self::LABEL_WIDTH + Self::BAR_WIDTH + 40;
+        set_attribute(&self.chart, "viewBox", &format!("0 0 {width} {height}"));
+        set_attribute(&self.chart, "width", &width.to_string());
+        set_attribute(&self.chart, "height", &height.to_string());
+
+        let max = self.entries.iter().map(|entry| entry.value).max().unwrap_or(0).max(1);
+        for (row, entry) in self.entries.iter().enumerate() {
+            let group = entry_group(document, &self.chart, entry);
+            let y = row * Self::ROW_HEIGHT;
+            let text_y = (y + Self::ROW_HEIGHT * 3 / 4).to_string();
+            let label = svg(document, &group, "text", &[("x", "0"), ("y", &text_y), ("font-size", "12")]);
+            label.set_text_content(Some(&entry.language));
+            let bar_width = (entry.value * Self::BAR_WIDTH / max).max(1);
+            svg(
+                document,
+                &group,
+                "rect",
+                &[
+                    ("x", &Self::LABEL_WIDTH.to_string()),
+                    ("y", &(y + 3).to_string()),
+                    ("width", &bar_width.to_string()),
+                    ("height", &(Self::ROW_HEIGHT - 6).to_string()),
+                    ("fill", &language_color(&entry.language)),
+                ],
+            );
+            let value_x = (Self::LABEL_WIDTH + bar_width + 4).to_string();
+            let value = svg(document, &group, "text", &[("x", &value_x), ("y", &text_y), ("font-size", "12")]);
+            value.set_text_content(Some(&entry.value.to_string()));
+        }
+    }
+}
+
+impl Component for BarChart {
+    type Props = Vec<ChartEntry>;
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let chart = create_svg(document, "svg");
+        let created = Self { chart, entries: props.clone() };
+        created.render(document);
+        created
+    }
+
+    fn update(&mut self, document: &Document, props: &Self::Props) {
+        if self.entries != *props {
+            self.entries = props.clone();
+            self.render(document);
+        }
+    }
+
+    fn root(&self) -> &Element {
+        &self.chart
+    }
+}
+
+/// Ring of segments proportional to the values of the entries.
+pub struct DonutChart {
+    chart: Element,
+    entries: Vec<ChartEntry>,
+}
+
+impl DonutChart {
+    /// Radius of a circle with a circumference of 100, so that dash lengths are percentages.
+    const RADIUS: &'static str = "15.9155";
+
+    pub fn with_size(self, size: usize) -> Self {
+        set_attribute(&self.chart, "width", &size.to_string());
+        set_attribute(&self.chart, "height", &size.to_string());
+        self
+    }
+
+    fn render(&self, document: &Document) {
+        self.chart.set_inner_html("");
+        let total: usize = self.entries.iter().map(|entry| entry.value).sum();
+        if total == 0 {
+            svg(document, &self.chart, "circle", &[("cx", "21"), ("cy", "21"), ("r", Self::RADIUS), ("fill", "none"), ("stroke", "lightgray"), ("stroke-width", "6")]);
+            return;
+        }
+        // segments start at 12 o'clock and run clockwise
+        let mut start = 0.0;
+        for entry in &self.entries {
+            let share = entry.value as f64 * 100.0 / total as f64;
+            let group = entry_group(document, &self.chart, entry);
+            svg(
+                document,
+                &group,
+                "circle",
+                &[
+                    ("cx", "21"),
+                    ("cy", "21"),
+                    ("r", Self::RADIUS),
+                    ("fill", "none"),
+                    ("stroke", &language_color(&entry.language)),
+                    ("stroke-width", "6"),
+                    ("stroke-dasharray", &format!("{share} {}", 100.0 - share)),
+                    ("stroke-dashoffset", &(25.0 - start).to_string()),
+                ],
+            );
+            start += share;
+        }
+    }
+}
+
+impl Component for DonutChart {
+    type Props = Vec<ChartEntry>;
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let chart = create_svg(document, "svg");
+        set_attribute(&chart, "viewBox", "0 0 42 42");
+        let created = Self { chart, entries: props.clone() };
+        created.render(document);
+        created.with_size(120)
+    }
+
+    fn update(&mut self, document: &Document, props: &Self::Props) {
+        if self.entries != *props {
+            self.entries = props.clone();
+            self.render(document);
+        }
+    }
+
+    fn root(&self) -> &Element {
+        &self.chart
+    }
+}
@@ -0,0 +1,63 @@
+use web_sys::{Document, Element, HtmlLiElement, HtmlUListElement};
+
+use super::{create, Component, KeyedList};
+
+/// A language with its number of repositories.
+struct LanguageItem {
+    item: HtmlLiElement,
+    props: (String, usize),
+}
+
+impl Component for LanguageItem {
+    type Props = (String, usize);
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let item: HtmlLiElement = create(document, "li");
+        let mut created = Self { item, props: props.clone() };
+        created.render();
+        created
+    }
+
+    fn update(&mut self, _document: &Document, props: &Self::Props) {
+        if &self.props != props {
+            self.props = props.clone();
+            self.render();
+        }
+    }
+
+    fn root(&self) -> &Element {
+        &self.item
+    }
+}
+
+impl LanguageItem {
+    fn render(&mut self) {
+        let (language, count) = &self.props;
+        self.item.set_text_content(Some(&format!("{language} ({count})")));
+    }
+}
+
+/// Languages with their repository counts, most used first.
+pub struct LanguageList {
+    list: KeyedList<LanguageItem>,
+}
+
+impl Component for LanguageList {
+    type Props = Vec<(String, usize)>;
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let list: HtmlUListElement = create(document, "ul");
+        let mut created = Self { list: KeyedList::new(list.into()) };
+        created.update(document, props);
+        created
+    }
+
+    fn update(&mut self, document: &Document, props: &Self::Props) {
+        let items = props.iter().map(|(language, count)| (language.clone(), (language.clone(), *count))).collect();
+        self.list.update(document, items);
+    }
+
+    fn root(&self) -> &Element {
+        self.list.container()
+    }
+}
@@ -0,0 +1,251 @@
+//! Members versus languages, colored by the number of repositories.
+//!
+//! Cells only count repositories, not bytes of code: the collected repositories carry just their
+//! main language, bytes per language would take a request per repository.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gh_client::stats::language_totals;
+use gh_client::{language_count, GHRepository, GHUser};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, Event, HtmlAnchorElement, HtmlDivElement, HtmlInputElement};
+
+use super::{append, create, listen, set_attribute, Component, KeyedList};
+use crate::router::Route;
+
+/// Column the rows are sorted by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sort {
+    Login,
+    /// Most repositories in the language first.
+    Language(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MatrixRowProps {
+    login: String,
+    /// Repository count of every visible language.
+    cells: Vec<(String, usize)>,
+}
+
+/// Background of a cell, the darker the more repositories.
+fn cell_color(count: usize, max: usize) -> String {
+    match count {
+        0 => "transparent".to_string(),
+        count => format!("hsl(210, 70%, {}%)", 90 - 55 * count / max.max(1)),
+    }
+}
+
+struct MatrixRow {
+    row: Element,
+    props: MatrixRowProps,
+    cells: Vec<Element>,
+    /// Largest count the cells are colored for, `None` until colored.
+    max: Option<usize>,
+}
+
+impl MatrixRow {
+    fn render(&mut self, document: &Document) {
+        self.row.set_inner_html("");
+        let MatrixRowProps { login, cells } = &self.props;
+        let name: HtmlAnchorElement = append(document, &append::<Element>(document, &self.row, "th"), "a");
+        name.set_href(&Route::User(login.clone()).to_hash());
+        name.set_text_content(Some(login));
+        self.cells = cells
+            .iter()
+            .map(|(language, count)| {
+                let cell: Element = append(document, &self.row, "td");
+                set_attribute(&cell, "title", &format!("{login}: {count} {language} repositories"));
+                if *count > 0 {
+                    cell.set_text_content(Some(&count.to_string()));
+                }
+                cell
+            })
+            .collect();
+        self.max = None;
+    }
+
+    /// Color the cells relative to `max`, the largest count of all visible cells.
+    fn recolor(&mut self, max: usize) {
+        if self.max == Some(max) {
+            return;
+        }
+        for (cell, (_, count)) in self.cells.iter().zip(&self.props.cells) {
+            set_attribute(cell, "style", &format!("background: {}; text-align: center; min-width: 2em;", cell_color(*count, max)));
+        }
+        self.max = Some(max);
+    }
+}
+
+impl Component for MatrixRow {
+    type Props = MatrixRowProps;
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let mut row = Self { row: create(document, "tr"), props: props.clone(), cells: Vec::new(), max: None };
+        row.render(document);
+        row
+    }
+
+    fn update(&mut self, document: &Document, props: &Self::Props) {
+        if self.props != *props {
+            self.props = props.clone();
+            self.render(document);
+        }
+    }
+
+    fn root(&self) -> &Element {
+        &self.row
+    }
+}
+
+struct State {
+    users: Vec<(String, HashMap<String, usize>)>,
+    /// All languages, the most used first.
+    languages: Vec<String>,
+    sort: Sort,
+    member_filter: String,
+    language_filter: String,
+}
+
+impl State {
+    /// Visible columns and rows, and the largest count of all visible cells.
+    ///
+    /// With a language filter, members without repositories in any visible language are hidden.
+    fn view(&self) -> (Vec<String>, Vec<MatrixRowProps>, usize) {
+        let language_filter = self.language_filter.to_ascii_lowercase();
+        let member_filter = self.member_filter.to_ascii_lowercase();
+        let columns: Vec<String> =
+            self.languages.iter().filter(|language| language.to_ascii_lowercase().contains(&language_filter)).cloned().collect();
+
+        let mut rows: Vec<MatrixRowProps> = self
+            .users
+            .iter()
+            .filter(|(login, _)| login.to_ascii_lowercase().contains(&member_filter))
+            .map(|(login, counts)| MatrixRowProps {
+                login: login.clone(),
+                cells: columns.iter().map(|language| (language.clone(), counts.get(language).copied().unwrap_or(0))).collect(),
+            })
+            .filter(|row| language_filter.is_empty() || row.cells.iter().any(|(_, count)| *count > 0))
+            .collect();
+        match &self.sort {
+            Sort::Login => rows.sort_by_key(|row| row.login.to_ascii_lowercase()),
+            Sort::Language(language) => {
+                let count = |row: &MatrixRowProps| row.cells.iter().find(|(cell, _)| cell == language).map(|(_, count)| *count).unwrap_or(0);
+                rows.sort_by(|a, b| count(b).cmp(&count(a)).then_with(|| a.login.to_ascii_lowercase().cmp(&b.login.to_ascii_lowercase())));
+            }
+        }
+        let max = rows.iter().flat_map(|row| &row.cells).map(|(_, count)| *count).max().unwrap_or(0);
+        (columns, rows, max)
+    }
+}
+
+struct Inner {
+    document: Document,
+    state: RefCell<State>,
+    head: Element,
+    rows: RefCell<KeyedList<MatrixRow>>,
+}
+
+impl Inner {
+    fn render(&self) {
+        let state = self.state.borrow();
+        let (columns, rows, max) = state.view();
+
+        // header cells carry the column they sort by, empty for the member column
+        self.head.set_inner_html("");
+        let header: Element = append(&self.document, &self.head, "tr");
+        let sorted = |sort: &Sort, title: &str| if state.sort == *sort { format!("{title} ▼") } else { title.to_string() };
+        let member: Element = append(&self.document, &header, "th");
+        set_attribute(&member, "data-sort", "");
+        member.set_text_content(Some(&sorted(&Sort::Login, "Member")));
+        for language in &columns {
+            let column: Element = append(&self.document, &header, "th");
+            set_attribute(&column, "data-sort", language);
+            set_attribute(&column, "style", "cursor: pointer;");
+            column.set_text_content(Some(&sorted(&Sort::Language(language.clone()), language)));
+        }
+
+        // rows only re-render when their own cells change, a different maximum only recolors them
+        let items = rows.into_iter().map(|row| (row.login.clone(), row)).collect();
+        let mut rows = self.rows.borrow_mut();
+        rows.update(&self.document, items);
+        rows.components_mut().for_each(|row| row.recolor(max));
+    }
+}
+
+/// Members (rows) versus languages (columns), colored by the number of repositories, sortable
+/// by clicking a column header and filterable by member and language.
+pub struct Matrix {
+    container: HtmlDivElement,
+    // the inputs and headers react as long as the component is kept
+    _on_filter: Closure<dyn Fn()>,
+    _on_sort: Closure<dyn Fn(Event)>,
+}
+
+impl Matrix {
+    pub fn new(document: &Document, org: &str, user_repos: &[(GHUser, Vec<GHRepository>)]) -> Self {
+        let container: HtmlDivElement = create(document, "div");
+        let back: HtmlAnchorElement = append(document, &container, "a");
+        back.set_href(&Route::Org(org.to_string()).to_hash());
+        back.set_text_content(Some(&format!("Back to {org}")));
+
+        let filters: HtmlDivElement = append(document, &container, "div");
+        let filter_input = |label: &str| {
+            append::<Element>(document, &filters, "span").set_text_content(Some(label));
+            let input: HtmlInputElement = append(document, &filters, "input");
+            input.set_type("text");
+            input.set_size(20);
+            input
+        };
+        let member_input = filter_input("Member: ");
+        let language_input = filter_input(" Language: ");
+
+        let table: Element = append(document, &container, "table");
+        let head: Element = append(document, &table, "thead");
+        let body: Element = append(document, &table, "tbody");
+        let state = State {
+            users: user_repos.iter().map(|(user, repos)| (user.login.clone(), language_count(repos))).collect(),
+            languages: language_totals(user_repos).into_iter().map(|total| total.language).collect(),
+            sort: Sort::Login,
+            member_filter: String::new(),
+            language_filter: String::new(),
+        };
+        let inner = Rc::new(Inner { document: document.clone(), state: RefCell::new(state), head, rows: RefCell::new(KeyedList::new(body)) });
+        inner.render();
+
+        let on_filter = {
+            let (inner, member_input, language_input) = (inner.clone(), member_input.clone(), language_input.clone());
+            Closure::<dyn Fn()>::new(move || {
+                {
+                    let mut state = inner.state.borrow_mut();
+                    state.member_filter = member_input.value().trim().to_string();
+                    state.language_filter = language_input.value().trim().to_string();
+                }
+                inner.render();
+            })
+        };
+        listen(&member_input, "input", &on_filter);
+        listen(&language_input, "input", &on_filter);
+
+        let on_sort = {
+            let inner = inner.clone();
+            Closure::<dyn Fn(_)>::new(move |event: Event| {
+                let Some(target) = event.target().and_then(|target| target.dyn_into::<Element>().ok()) else { return };
+                let Some(column) = target.closest("[data-sort]").ok().flatten().and_then(|header| header.get_attribute("data-sort")) else {
+                    return;
+                };
+                inner.state.borrow_mut().sort = if column.is_empty() { Sort::Login } else { Sort::Language(column) };
+                inner.render();
+            })
+        };
+        listen(&inner.head, "click", &on_sort);
+
+        Self { container, _on_filter: on_filter, _on_sort: on_sort }
+    }
+
+    pub fn root(&self) -> &Element {
+        &self.container
+    }
+}
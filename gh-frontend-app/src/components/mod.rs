@@ -0,0 +1,122 @@
+//! Reusable pieces of the page that update their DOM nodes in place instead of being rebuilt.
+//!
+//! A [Component] owns the nodes it created and compares new props with the ones it rendered,
+//! touching the DOM only where they differ. A [KeyedList] does the same for a list of components,
+//! matching children by key so that reordering, adding or removing items keeps the nodes of all
+//! other items.
+use std::collections::HashMap;
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, EventTarget, Node};
+
+mod charts;
+mod language_list;
+mod matrix;
+mod progress;
+mod search_bar;
+mod user_card;
+mod user_page;
+
+pub use charts::{BarChart, ChartEntry, DonutChart};
+pub use language_list::LanguageList;
+pub use matrix::Matrix;
+pub use progress::Progress;
+pub use search_bar::SearchBar;
+pub use user_card::{UserCard, UserCardProps};
+pub use user_page::{UserPage, UserPageProps};
+
+/// Create an element with tag `tag`, typed as `T`, for the caller to place.
+pub fn create<T: JsCast>(document: &Document, tag: &str) -> T {
+    document.create_element(tag).expect("invalid tag").unchecked_into()
+}
+
+/// Create an element with tag `tag`, typed as `T`, and append it to `parent`.
+pub fn append<T: JsCast>(document: &Document, parent: &Node, tag: &str) -> T {
+    let element: Element = create(document, tag);
+    append_node(parent, &element);
+    element.unchecked_into()
+}
+
+/// Append an existing node, e.g. the root of a component, to `parent`.
+pub fn append_node(parent: &Node, node: &Node) {
+    parent.append_child(node).expect("failed to append node");
+}
+
+/// Set an attribute, which only fails for invalid attribute names.
+pub fn set_attribute(element: &Element, name: &str, value: &str) {
+    element.set_attribute(name, value).expect("invalid attribute name");
+}
+
+/// Call `listener` on every `event` of `target`, for as long as the listener is kept.
+pub fn listen<T: ?Sized>(target: &EventTarget, event: &str, listener: &Closure<T>) {
+    target.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref()).expect("failed to add listener");
+}
+
+pub trait Component {
+    type Props: PartialEq;
+
+    fn create(document: &Document, props: &Self::Props) -> Self;
+
+    /// Bring the DOM in line with `props`.
+    fn update(&mut self, document: &Document, props: &Self::Props);
+
+    /// The outermost node, placed by the parent.
+    fn root(&self) -> &Element;
+}
+
+/// Children of `container` rendered from keyed props.
+pub struct KeyedList<C: Component> {
+    container: Element,
+    items: Vec<(String, C)>,
+}
+
+impl<C: Component> KeyedList<C> {
+    pub fn new(container: Element) -> Self {
+        Self { container, items: Vec::new() }
+    }
+
+    pub fn container(&self) -> &Element {
+        &self.container
+    }
+
+    /// The rendered components, in order.
+    pub fn components_mut(&mut self) -> impl Iterator<Item = &mut C> {
+        self.items.iter_mut().map(|(_, component)| component)
+    }
+
+    /// Render `items` in the given order, reusing the components of keys rendered before.
+    pub fn update(&mut self, document: &Document, items: Vec<(String, C::Props)>) {
+        let mut previous: HashMap<String, C> = self.items.drain(..).collect();
+        let mut next = Vec::with_capacity(items.len());
+        for (key, props) in items {
+            let component = match previous.remove(&key) {
+                Some(mut component) => {
+                    component.update(document, &props);
+                    component
+                }
+                None => {
+                    let component = C::create(document, &props);
+                    set_attribute(component.root(), "data-key", &key);
+                    component
+                }
+            };
+            next.push((key, component));
+        }
+        for component in previous.into_values() {
+            component.root().remove();
+        }
+
+        // move only the nodes that are out of place
+        let mut expected = self.container.first_element_child();
+        for (_, component) in &next {
+            let node = component.root();
+            if expected.as_ref() == Some(node) {
+                expected = node.next_element_sibling();
+            } else {
+                self.container.insert_before(node, expected.as_ref().map(|element| element.as_ref())).expect("failed to move node");
+            }
+        }
+        self.items = next;
+    }
+}
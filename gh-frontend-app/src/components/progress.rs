@@ -0,0 +1,44 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, HtmlButtonElement, HtmlDivElement, HtmlLabelElement, HtmlProgressElement};
+
+use super::{append, create};
+
+/// Progress bar with a label and a cancel button.
+pub struct Progress {
+    container: HtmlDivElement,
+    label: HtmlLabelElement,
+    bar: HtmlProgressElement,
+    // dropping the listener with the component detaches it from the removed button
+    _on_cancel: Closure<dyn Fn()>,
+}
+
+impl Progress {
+    pub fn new(document: &Document, label: &str, on_cancel: impl Fn() + 'static) -> Self {
+        let container: HtmlDivElement = create(document, "div");
+        let label_element: HtmlLabelElement = append(document, &container, "label");
+        label_element.set_text_content(Some(label));
+        let bar: HtmlProgressElement = append(document, &container, "progress");
+        let cancel: HtmlButtonElement = append(document, &container, "button");
+        cancel.set_text_content(Some("Cancel"));
+        let on_cancel = Closure::<dyn Fn()>::new(on_cancel);
+        cancel.set_onclick(Some(on_cancel.as_ref().unchecked_ref()));
+        Self { container, label: label_element, bar, _on_cancel: on_cancel }
+    }
+
+    pub fn set(&self, label: &str, done: usize, total: usize) {
+        self.label.set_text_content(Some(label));
+        self.bar.set_max(total as f64);
+        self.bar.set_value(done as f64);
+    }
+
+    pub fn root(&self) -> &Element {
+        &self.container
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.container.remove();
+    }
+}
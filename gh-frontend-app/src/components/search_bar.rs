@@ -0,0 +1,38 @@
+use wasm_bindgen::closure::Closure;
+use web_sys::{Document, Element, HtmlDivElement, HtmlInputElement};
+
+use super::{append, create, listen};
+
+/// Labelled text input reporting every change of its value.
+pub struct SearchBar {
+    bar: HtmlDivElement,
+    input: HtmlInputElement,
+    // the input reports its changes as long as the component is kept
+    _on_input: Closure<dyn Fn()>,
+}
+
+impl SearchBar {
+    pub fn new(document: &Document, label: &str, on_input: impl Fn(String) + 'static) -> Self {
+        let bar: HtmlDivElement = create(document, "div");
+        bar.set_text_content(Some(label));
+        let input: HtmlInputElement = append(document, &bar, "input");
+        input.set_type("text");
+        input.set_size(20);
+
+        let on_input = {
+            let input = input.clone();
+            Closure::<dyn Fn()>::new(move || on_input(input.value()))
+        };
+        listen(&input, "input", &on_input);
+        Self { bar, input, _on_input: on_input }
+    }
+
+    /// Replace the value, without reporting it as input.
+    pub fn set_value(&self, value: &str) {
+        self.input.set_value(value);
+    }
+
+    pub fn root(&self) -> &Element {
+        &self.bar
+    }
+}
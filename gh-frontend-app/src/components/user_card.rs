@@ -0,0 +1,83 @@
+use gh_client::GHUser;
+use web_sys::{Document, Element, HtmlAnchorElement, HtmlDivElement, HtmlImageElement};
+
+use super::{append, append_node, create, set_attribute, ChartEntry, Component, DonutChart, LanguageList};
+use crate::router::Route;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserCardProps {
+    pub user: GHUser,
+    /// Languages with their repository counts, most used first.
+    pub languages: Vec<(String, usize)>,
+}
+
+/// Name, avatar and languages of a user, linking to the page of the user.
+pub struct UserCard {
+    card: HtmlDivElement,
+    name: HtmlAnchorElement,
+    avatar: HtmlImageElement,
+    chart: DonutChart,
+    languages: LanguageList,
+    props: UserCardProps,
+}
+
+impl Component for UserCard {
+    type Props = UserCardProps;
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let card: HtmlDivElement = create(document, "div");
+        let heading: Element = append(document, &card, "h1");
+        let name: HtmlAnchorElement = append(document, &heading, "a");
+        let avatar_and_languages: HtmlDivElement = append(document, &card, "div");
+        set_attribute(&avatar_and_languages, "style", "display: flex; align-items: center;");
+        let avatar: HtmlImageElement = append(document, &avatar_and_languages, "img");
+        avatar.set_width(200);
+        avatar.set_height(200);
+        let chart = DonutChart::create(document, &chart_entries(&props.languages)).with_size(80);
+        append_node(&avatar_and_languages, chart.root());
+        let languages = LanguageList::create(document, &props.languages);
+        let languages_p: Element = append(document, &avatar_and_languages, "p");
+        append_node(&languages_p, languages.root());
+
+        let card = Self { card, name, avatar, chart, languages, props: props.clone() };
+        card.render_user();
+        card
+    }
+
+    fn update(&mut self, document: &Document, props: &Self::Props) {
+        if self.props == *props {
+            return;
+        }
+        if self.props.user != props.user {
+            self.props.user = props.user.clone();
+            self.render_user();
+        }
+        if self.props.languages != props.languages {
+            self.props.languages = props.languages.clone();
+            self.chart.update(document, &chart_entries(&props.languages));
+            self.languages.update(document, &props.languages);
+        }
+    }
+
+    fn root(&self) -> &Element {
+        &self.card
+    }
+}
+
+/// Segments of the repositories per language.
+pub fn chart_entries(languages: &[(String, usize)]) -> Vec<ChartEntry> {
+    languages
+        .iter()
+        .map(|(language, count)| ChartEntry { language: language.clone(), value: *count, tooltip: format!("{language}: {count} repositories") })
+        .collect()
+}
+
+impl UserCard {
+    fn render_user(&self) {
+        let user = &self.props.user;
+        self.name.set_text_content(Some(&user.login));
+        self.name.set_href(&Route::User(user.login.clone()).to_hash());
+        self.avatar.set_src(&user.avatar_url);
+        self.avatar.set_alt(&user.login);
+    }
+}
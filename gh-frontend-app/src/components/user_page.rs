@@ -0,0 +1,103 @@
+use gh_client::{language_count, GHRepository, GHUser};
+use web_sys::{Document, Element, HtmlAnchorElement, HtmlDivElement, HtmlImageElement};
+
+use super::user_card::chart_entries;
+use super::{append, append_node, create, set_attribute, Component, DonutChart, LanguageList};
+use crate::router::Route;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserPageProps {
+    pub org: String,
+    pub user: GHUser,
+    pub repos: Vec<GHRepository>,
+}
+
+/// Profile, language breakdown and repositories of a single member.
+pub struct UserPage {
+    page: HtmlDivElement,
+    props: UserPageProps,
+}
+
+impl Component for UserPage {
+    type Props = UserPageProps;
+
+    fn create(document: &Document, props: &Self::Props) -> Self {
+        let page: HtmlDivElement = create(document, "div");
+        let page = Self { page, props: props.clone() };
+        page.render(document);
+        page
+    }
+
+    fn update(&mut self, document: &Document, props: &Self::Props) {
+        if self.props != *props {
+            self.props = props.clone();
+            self.page.set_inner_html("");
+            self.render(document);
+        }
+    }
+
+    fn root(&self) -> &Element {
+        &self.page
+    }
+}
+
+impl UserPage {
+    fn render(&self, document: &Document) {
+        let UserPageProps { org, user, repos } = &self.props;
+
+        let back: HtmlAnchorElement = append(document, &self.page, "a");
+        back.set_href(&Route::Org(org.clone()).to_hash());
+        back.set_text_content(Some(&format!("Back to {org}")));
+
+        let profile: HtmlDivElement = append(document, &self.page, "div");
+        set_attribute(&profile, "style", "display: flex; align-items: center; gap: 1em;");
+        let avatar: HtmlImageElement = append(document, &profile, "img");
+        avatar.set_src(&user.avatar_url);
+        avatar.set_alt(&user.login);
+        avatar.set_width(120);
+        avatar.set_height(120);
+        let name: Element = append(document, &profile, "h1");
+        let github: HtmlAnchorElement = append(document, &name, "a");
+        github.set_href(&format!("https://github.com/{}", user.login));
+        github.set_text_content(Some(&user.login));
+
+        // languages with their share of the repositories that have one
+        let mut languages: Vec<(String, usize)> = language_count(repos).into_iter().collect();
+        languages.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        let total: usize = languages.iter().map(|(_, count)| count).sum();
+        let breakdown: Vec<(String, usize)> = languages
+            .iter()
+            .map(|(language, count)| (format!("{language}, {}%", count * 100 / total.max(1)), *count))
+            .collect();
+        append::<Element>(document, &self.page, "h2").set_text_content(Some("Languages"));
+        let chart_and_list: HtmlDivElement = append(document, &self.page, "div");
+        set_attribute(&chart_and_list, "style", "display: flex; align-items: center; gap: 1em;");
+        append_node(&chart_and_list, DonutChart::create(document, &chart_entries(&languages)).with_size(160).root());
+        append_node(&chart_and_list, LanguageList::create(document, &breakdown).root());
+
+        append::<Element>(document, &self.page, "h2").set_text_content(Some(&format!("Repositories ({})", repos.len())));
+        let table: Element = append(document, &self.page, "table");
+        let header: Element = append(document, &table, "tr");
+        for title in ["Name", "Language", "Stars", "Last push"] {
+            append::<Element>(document, &header, "th").set_text_content(Some(title));
+        }
+        // most recently pushed first, repositories without push time last
+        let mut repos: Vec<&GHRepository> = repos.iter().collect();
+        repos.sort_by(|a, b| b.pushed_at.cmp(&a.pushed_at).then_with(|| a.name.cmp(&b.name)));
+        for repo in repos {
+            let row: Element = append(document, &table, "tr");
+            let name: Element = append(document, &row, "td");
+            if repo.html_url.is_empty() {
+                name.set_text_content(Some(&repo.name));
+            } else {
+                let link: HtmlAnchorElement = append(document, &name, "a");
+                link.set_href(&repo.html_url);
+                link.set_text_content(Some(&repo.name));
+            }
+            append::<Element>(document, &row, "td").set_text_content(repo.language.as_deref());
+            append::<Element>(document, &row, "td").set_text_content(Some(&repo.stargazers_count.to_string()));
+            let pushed_at = repo.pushed_at.map(|pushed_at| pushed_at.format("%Y-%m-%d").to_string());
+            append::<Element>(document, &row, "td").set_text_content(Some(pushed_at.as_deref().unwrap_or("unknown")));
+        }
+    }
+}
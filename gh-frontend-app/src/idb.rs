@@ -0,0 +1,262 @@
+//! Persistence of fetched data in IndexedDB, which unlike local storage is not limited to a few
+//! megabytes of strings.
+//!
+//! | store    | key                        | value                                             |
+//! |----------|----------------------------|---------------------------------------------------|
+//! | `orgs`   | organization (lower case)  | [GHSnapshot] JSON without data, plus member order |
+//! | `users`  | `{organization}/{login}`   | `[user, [repository, ...]]` JSON                  |
+//! | `crawls` | organization (lower case)  | [CrawlProgress] JSON of an unfinished crawl       |
+//!
+//! Data cached in local storage by earlier versions is moved here by [Database::migrate_local_storage].
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use futures::channel::oneshot;
+use gh_client::crawl::CrawlProgress;
+use gh_client::snapshot::GHSnapshot;
+use gh_client::{GHRepository, GHUser};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{EventTarget, IdbDatabase, IdbKeyRange, IdbObjectStore, IdbOpenDbRequest, IdbRequest, IdbRequestReadyState, IdbTransaction, IdbTransactionMode, Window};
+
+const DATABASE_NAME: &str = "gh-frontend-app";
+const DATABASE_VERSION: u32 = 1;
+const ORGS: &str = "orgs";
+const USERS: &str = "users";
+const CRAWLS: &str = "crawls";
+
+/// Local storage key prefix of data cached by earlier versions, followed by `-{organization}`
+/// or nothing for the very first versions.
+const LEGACY_USER_REPOSITORIES_STORAGE_KEY: &str = "gh-frontend-app-user-repositories";
+/// Local storage key of crawl progress kept by earlier versions.
+const LEGACY_CRAWL_PROGRESS_STORAGE_KEY: &str = "gh-frontend-app-crawl-progress";
+
+/// Record of the `orgs` store.
+#[derive(Serialize, Deserialize)]
+struct OrgRecord {
+    /// Snapshot metadata, its data lives in the `users` store. Kept as JSON value so that
+    /// records of older snapshot versions are migrated by [GHSnapshot::from_json].
+    snapshot: serde_json::Value,
+    /// Logins in the order of the snapshot data.
+    logins: Vec<String>,
+}
+
+fn js_error(context: &str, error: JsValue) -> anyhow::Error {
+    anyhow!("{context}: {error:?}")
+}
+
+type Listener = Closure<dyn FnMut()>;
+
+/// Wait for the first `success` or `failures` event of `target`, `true` if it was `success`.
+async fn next_event(target: &EventTarget, success: &str, failures: &[&str]) -> bool {
+    let (sender, receiver) = oneshot::channel::<bool>();
+    let sender = Rc::new(RefCell::new(Some(sender)));
+    let listeners: Vec<(&str, Listener)> = std::iter::once((success, true))
+        .chain(failures.iter().map(|failure| (*failure, false)))
+        .map(|(event, succeeded)| {
+            let sender = sender.clone();
+            let listener = Listener::new(move || {
+                if let Some(sender) = sender.borrow_mut().take() {
+                    sender.send(succeeded).ok();
+                }
+            });
+            target.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref()).unwrap();
+            (event, listener)
+        })
+        .collect();
+    let succeeded = receiver.await.unwrap_or(false);
+    for (event, listener) in listeners {
+        target.remove_event_listener_with_callback(event, listener.as_ref().unchecked_ref()).unwrap();
+    }
+    succeeded
+}
+
+/// Result of a request once it finished.
+async fn finished(request: IdbRequest) -> Result<JsValue> {
+    if next_event(&request, "success", &["error"]).await {
+        request.result().map_err(|error| js_error("Failed to read request result", error))
+    } else {
+        Err(anyhow!("IndexedDB request failed: {:?}", request.error()))
+    }
+}
+
+/// Wait until all requests of a transaction are committed.
+async fn committed(transaction: IdbTransaction) -> Result<()> {
+    if next_event(&transaction, "complete", &["error", "abort"]).await {
+        Ok(())
+    } else {
+        Err(anyhow!("IndexedDB transaction failed: {:?}", transaction.error()))
+    }
+}
+
+/// Keys of the `users` store belonging to `org`.
+fn user_range(org: &str) -> Result<JsValue> {
+    IdbKeyRange::bound(&JsValue::from_str(&format!("{org}/")), &JsValue::from_str(&format!("{org}/\u{ffff}")))
+        .map(JsValue::from)
+        .map_err(|error| js_error("Failed to create key range", error))
+}
+
+#[derive(Debug, Clone)]
+pub struct Database(IdbDatabase);
+
+impl Database {
+    /// Open the database, creating its stores on first use.
+    pub async fn open(window: &Window) -> Result<Self> {
+        let factory = window
+            .indexed_db()
+            .map_err(|error| js_error("Failed to access IndexedDB", error))?
+            .ok_or_else(|| anyhow!("IndexedDB is not available"))?;
+        let request: IdbOpenDbRequest = factory
+            .open_with_u32(DATABASE_NAME, DATABASE_VERSION)
+            .map_err(|error| js_error("Failed to open IndexedDB", error))?;
+
+        // stores can only be created while upgrading, before the open request succeeds
+        let on_upgrade = {
+            let request = request.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                let database: IdbDatabase = request.result().unwrap().unchecked_into();
+                for store in [ORGS, USERS, CRAWLS] {
+                    if !database.object_store_names().contains(store) {
+                        database.create_object_store(store).unwrap();
+                    }
+                }
+            })
+        };
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        // other tabs with the database open at an older version block the upgrade until they close
+        if next_event(&request, "success", &["error", "blocked"]).await {
+            request.set_onupgradeneeded(None);
+            let database = request.result().map_err(|error| js_error("Failed to read opened database", error))?;
+            return Ok(Self(database.unchecked_into()));
+        }
+        if request.ready_state() == IdbRequestReadyState::Pending {
+            // the upgrade still runs once the other tabs close, it must not miss its listener
+            on_upgrade.forget();
+            return Err(anyhow!("IndexedDB is blocked by another tab of this app, close it and reload"));
+        }
+        request.set_onupgradeneeded(None);
+        Err(anyhow!("Failed to open IndexedDB: {:?}", request.error()))
+    }
+
+    fn transaction(&self, stores: &[&str], mode: IdbTransactionMode) -> Result<IdbTransaction> {
+        let names: js_sys::Array = stores.iter().map(|store| JsValue::from_str(store)).collect();
+        self.0.transaction_with_str_sequence_and_mode(&names, mode).map_err(|error| js_error("Failed to start transaction", error))
+    }
+
+    fn store(transaction: &IdbTransaction, name: &str) -> Result<IdbObjectStore> {
+        transaction.object_store(name).map_err(|error| js_error("Failed to access object store", error))
+    }
+
+    /// The cached data of `org`, if any.
+    pub async fn load_snapshot(&self, org: &str) -> Result<Option<GHSnapshot>> {
+        let org = org.to_ascii_lowercase();
+        let transaction = self.transaction(&[ORGS, USERS], IdbTransactionMode::Readonly)?;
+        let record = Self::store(&transaction, ORGS)?.get(&JsValue::from_str(&org));
+        let users = Self::store(&transaction, USERS)?.get_all_with_key(&user_range(&org)?);
+        // wait for both at once, awaiting one after the other would miss the events of the second
+        let (record, users) = futures::join!(
+            finished(record.map_err(|error| js_error("Failed to read organization", error))?),
+            finished(users.map_err(|error| js_error("Failed to read users", error))?)
+        );
+        let (record, users) = (record?, users?);
+
+        let Some(record) = record.as_string() else { return Ok(None) };
+        let OrgRecord { snapshot, logins } = serde_json::from_str(&record)?;
+        let mut snapshot = GHSnapshot::from_json(&snapshot.to_string())?;
+        let mut data: HashMap<String, (GHUser, Vec<GHRepository>)> = HashMap::new();
+        for user in js_sys::Array::from(&users).iter() {
+            let user: (GHUser, Vec<GHRepository>) = serde_json::from_str(&user.as_string().unwrap_or_default())?;
+            data.insert(user.0.login.clone(), user);
+        }
+        snapshot.data = logins.iter().filter_map(|login| data.remove(login)).collect();
+        Ok(Some(snapshot))
+    }
+
+    /// Replace the cached data of the organization of `snapshot`.
+    pub async fn store_snapshot(&self, snapshot: &GHSnapshot) -> Result<()> {
+        let org = snapshot.org.to_ascii_lowercase();
+        let transaction = self.transaction(&[ORGS, USERS], IdbTransactionMode::Readwrite)?;
+        let users = Self::store(&transaction, USERS)?;
+        users.delete(&user_range(&org)?).map_err(|error| js_error("Failed to delete users", error))?;
+        for user in &snapshot.data {
+            let key = JsValue::from_str(&format!("{org}/{}", user.0.login));
+            users
+                .put_with_key(&JsValue::from_str(&serde_json::to_string(user)?), &key)
+                .map_err(|error| js_error("Failed to store user", error))?;
+        }
+        let record = OrgRecord {
+            snapshot: serde_json::to_value(GHSnapshot { data: Vec::new(), ..snapshot.clone() })?,
+            logins: snapshot.data.iter().map(|(user, _)| user.login.clone()).collect(),
+        };
+        Self::store(&transaction, ORGS)?
+            .put_with_key(&JsValue::from_str(&serde_json::to_string(&record)?), &JsValue::from_str(&org))
+            .map_err(|error| js_error("Failed to store organization", error))?;
+        committed(transaction).await
+    }
+
+    /// Progress of an unfinished crawl of `org`, if any.
+    pub async fn load_crawl(&self, org: &str) -> Result<Option<CrawlProgress>> {
+        let transaction = self.transaction(&[CRAWLS], IdbTransactionMode::Readonly)?;
+        let request = Self::store(&transaction, CRAWLS)?
+            .get(&JsValue::from_str(&org.to_ascii_lowercase()))
+            .map_err(|error| js_error("Failed to read crawl", error))?;
+        match finished(request).await?.as_string() {
+            Some(json) => Ok(Some(CrawlProgress::from_json(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn store_crawl(&self, progress: &CrawlProgress) -> Result<()> {
+        let transaction = self.transaction(&[CRAWLS], IdbTransactionMode::Readwrite)?;
+        Self::store(&transaction, CRAWLS)?
+            .put_with_key(&JsValue::from_str(&progress.to_json()?), &JsValue::from_str(&progress.org.to_ascii_lowercase()))
+            .map_err(|error| js_error("Failed to store crawl", error))?;
+        committed(transaction).await
+    }
+
+    pub async fn delete_crawl(&self, org: &str) -> Result<()> {
+        let transaction = self.transaction(&[CRAWLS], IdbTransactionMode::Readwrite)?;
+        Self::store(&transaction, CRAWLS)?
+            .delete(&JsValue::from_str(&org.to_ascii_lowercase()))
+            .map_err(|error| js_error("Failed to delete crawl", error))?;
+        committed(transaction).await
+    }
+
+    /// Move data and crawl progress cached in local storage by earlier versions into the database.
+    ///
+    /// Snapshots that did not record their organization were written for `codecentric`, the only
+    /// organization the first versions could show.
+    pub async fn migrate_local_storage(&self, window: &Window) -> Result<()> {
+        let local_storage = window.local_storage().unwrap().unwrap();
+        let keys: Vec<String> = (0..local_storage.length().unwrap())
+            .filter_map(|index| local_storage.key(index).unwrap())
+            .filter(|key| key.starts_with(LEGACY_USER_REPOSITORIES_STORAGE_KEY))
+            .collect();
+        for key in keys {
+            let json = local_storage.get(&key).unwrap().unwrap_or_default();
+            match GHSnapshot::from_json(&json) {
+                Ok(mut snapshot) => {
+                    if snapshot.org.is_empty() {
+                        snapshot.org = "codecentric".to_string();
+                    }
+                    log::info!("Moving cached data of {} from local storage to IndexedDB", snapshot.org);
+                    self.store_snapshot(&snapshot).await?;
+                }
+                Err(msg) => log::warn!("Discarding unreadable data from local storage: {msg}"),
+            }
+            local_storage.remove_item(&key).unwrap();
+        }
+
+        if let Some(json) = local_storage.get(LEGACY_CRAWL_PROGRESS_STORAGE_KEY).unwrap() {
+            if let Ok(progress) = CrawlProgress::from_json(&json) {
+                log::info!("Moving crawl progress of {} from local storage to IndexedDB", progress.org);
+                self.store_crawl(&progress).await?;
+            }
+            local_storage.remove_item(LEGACY_CRAWL_PROGRESS_STORAGE_KEY).unwrap();
+        }
+        Ok(())
+    }
+}
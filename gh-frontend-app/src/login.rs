@@ -0,0 +1,216 @@
+//! Login dialog for the GH-API token, validating it before it is saved in local storage.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures::channel::oneshot;
+use gh_client::{GHClient, GHError, GHTokenInfo};
+use surf::{Client, StatusCode};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Document, Event, HtmlButtonElement, HtmlDialogElement, HtmlDivElement, HtmlFormElement, HtmlInputElement, HtmlParagraphElement, Window};
+
+const TOKEN_STORAGE_KEY: &str = "gh-frontend-app-api-token";
+
+/// A validated GH-API token.
+pub struct Login {
+    pub token: String,
+    pub info: GHTokenInfo,
+}
+
+async fn validate(token: &str) -> anyhow::Result<GHTokenInfo> {
+    GHClient::new(Client::new(), Some(token.to_string())).get_token_info().await
+}
+
+/// Whether the GH-API rejected the token itself, rather than e.g. the network or rate limit failing.
+fn is_rejected(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<GHError>(), Some(GHError::Status(StatusCode::Unauthorized)))
+}
+
+fn describe_scopes(info: &GHTokenInfo) -> String {
+    match &info.scopes {
+        Some(scopes) if scopes.is_empty() => "no scopes".to_string(),
+        Some(scopes) => format!("scopes: {}", scopes.join(", ")),
+        None => "fine-grained token".to_string(),
+    }
+}
+
+/// Load the GH-API token from local storage, asking for one with the login dialog if there is
+/// none or the GH-API rejects the stored one.
+///
+/// Other failures to validate the stored token keep it and offer to retry.
+/// Returns `None` if the user cancels the dialog.
+pub async fn login(window: &Window) -> Option<Login> {
+    let local_storage = window.local_storage().unwrap().unwrap();
+    let mut error = None;
+    if let Some(token) = local_storage.get(TOKEN_STORAGE_KEY).unwrap() {
+        loop {
+            match validate(&token).await {
+                Ok(info) => return Some(Login { token, info }),
+                Err(msg) if is_rejected(&msg) => {
+                    log::warn!("Stored GH-API token is no longer valid: {msg}");
+                    local_storage.remove_item(TOKEN_STORAGE_KEY).unwrap();
+                    error = Some(format!("The stored token is no longer valid: {msg}"));
+                    break;
+                }
+                Err(msg) => {
+                    log::warn!("Failed to validate stored GH-API token: {msg}");
+                    wait_for_retry(window, &format!("Failed to check the stored token: {msg}")).await;
+                }
+            }
+        }
+    }
+    let login = show_dialog(window, error).await?;
+    local_storage.set(TOKEN_STORAGE_KEY, &login.token).expect("Failed to store token in local storage.");
+    Some(login)
+}
+
+/// Show `message` with a button to retry, until it is clicked.
+async fn wait_for_retry(window: &Window, message: &str) {
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+
+    let notice: HtmlDivElement = root.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
+    notice.append_child(&document.create_element("p").unwrap()).unwrap().set_text_content(Some(message));
+    let retry: HtmlButtonElement = notice.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+    retry.set_text_content(Some("Retry"));
+
+    let (sender, receiver) = oneshot::channel::<()>();
+    let sender = RefCell::new(Some(sender));
+    let on_click = Closure::<dyn Fn()>::new(move || {
+        if let Some(sender) = sender.borrow_mut().take() {
+            sender.send(()).ok();
+        }
+    });
+    retry.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+    receiver.await.ok();
+    retry.remove_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+    root.remove_child(&notice).unwrap();
+}
+
+/// Forget the stored GH-API token and start over.
+pub fn logout(window: &Window) {
+    let local_storage = window.local_storage().unwrap().unwrap();
+    local_storage.remove_item(TOKEN_STORAGE_KEY).unwrap();
+    window.location().reload().unwrap();
+}
+
+/// Ask for a token until a valid one is entered or the dialog is cancelled.
+async fn show_dialog(window: &Window, error: Option<String>) -> Option<Login> {
+    let document: Document = window.document().expect("no document?");
+    let body = document.body().expect("no body?");
+
+    let dialog: HtmlDialogElement = body.append_child(&document.create_element("dialog").unwrap()).unwrap().unchecked_into();
+    let form: HtmlFormElement = dialog.append_child(&document.create_element("form").unwrap()).unwrap().unchecked_into();
+    form.append_child(&document.create_element("p").unwrap())
+        .unwrap()
+        .set_text_content(Some("Please provide a GH-API token. It is only stored in this browser."));
+    let input: HtmlInputElement = form.append_child(&document.create_element("input").unwrap()).unwrap().unchecked_into();
+    input.set_attribute("type", "password").unwrap();
+    input.set_attribute("placeholder", "ghp_...").unwrap();
+    let status: HtmlParagraphElement = form.append_child(&document.create_element("p").unwrap()).unwrap().unchecked_into();
+    status.set_text_content(error.as_deref());
+    let submit: HtmlButtonElement = form.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+    submit.set_text_content(Some("Log in"));
+    let cancel: HtmlButtonElement = form.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+    cancel.set_text_content(Some("Cancel"));
+    cancel.set_type("button");
+
+    // completed by a validated token, or with `None` once the dialog closes without one
+    let (sender, receiver) = oneshot::channel::<Option<Login>>();
+    let sender = Rc::new(RefCell::new(Some(sender)));
+
+    let on_submit = {
+        let (dialog, input, status, submit, sender) = (dialog.clone(), input.clone(), status.clone(), submit.clone(), sender.clone());
+        Closure::<dyn Fn(_)>::new(move |event: Event| {
+            event.prevent_default();
+            let token = input.value().trim().to_string();
+            if token.is_empty() {
+                return;
+            }
+            let (dialog, status, submit, sender) = (dialog.clone(), status.clone(), submit.clone(), sender.clone());
+            status.set_text_content(Some("Checking token..."));
+            submit.set_disabled(true);
+            spawn_local(async move {
+                match validate(&token).await {
+                    Ok(info) => {
+                        status.set_text_content(Some(&format!("Logged in as {}, {}", info.login, describe_scopes(&info))));
+                        if let Some(sender) = sender.borrow_mut().take() {
+                            sender.send(Some(Login { token, info })).ok();
+                        }
+                        dialog.close();
+                    }
+                    Err(msg) => {
+                        status.set_text_content(Some(&format!("Invalid token: {msg}")));
+                        submit.set_disabled(false);
+                    }
+                }
+            });
+        })
+    };
+    form.add_event_listener_with_callback("submit", on_submit.as_ref().unchecked_ref()).unwrap();
+    on_submit.forget();
+
+    let on_cancel = {
+        let dialog = dialog.clone();
+        Closure::<dyn Fn()>::new(move || dialog.close())
+    };
+    cancel.add_event_listener_with_callback("click", on_cancel.as_ref().unchecked_ref()).unwrap();
+    on_cancel.forget();
+
+    // also fired when the dialog is dismissed with escape
+    let on_close = {
+        let sender = sender.clone();
+        Closure::<dyn Fn()>::new(move || {
+            if let Some(sender) = sender.borrow_mut().take() {
+                sender.send(None).ok();
+            }
+        })
+    };
+    dialog.add_event_listener_with_callback("close", on_close.as_ref().unchecked_ref()).unwrap();
+    on_close.forget();
+
+    dialog.show_modal().unwrap();
+    let login = receiver.await.ok().flatten();
+    body.remove_child(&dialog).unwrap();
+    login
+}
+
+/// Show who is logged in with which scopes, and a button to forget the token.
+pub fn render_session(window: &Window, info: &GHTokenInfo) {
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+
+    let session: HtmlDivElement = document.create_element("div").unwrap().unchecked_into();
+    root.insert_before(&session, root.first_child().as_ref()).unwrap();
+    session
+        .append_child(&document.create_element("span").unwrap())
+        .unwrap()
+        .set_text_content(Some(&format!("Logged in as {} ({}) ", info.login, describe_scopes(info))));
+    let logout_button: HtmlButtonElement = session.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+    logout_button.set_text_content(Some("Log out"));
+    let on_click = {
+        let window = window.clone();
+        Closure::<dyn Fn()>::new(move || logout(&window))
+    };
+    logout_button.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+    on_click.forget();
+}
+
+/// Explain that a token is needed, with a button to open the login dialog again.
+pub fn render_logged_out(window: &Window) {
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+
+    root.append_child(&document.create_element("p").unwrap())
+        .unwrap()
+        .set_text_content(Some("A GH-API token is required to fetch the organization from GitHub."));
+    let login_button: HtmlButtonElement = root.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+    login_button.set_text_content(Some("Log in"));
+    let on_click = {
+        let window = window.clone();
+        Closure::<dyn Fn()>::new(move || window.location().reload().unwrap())
+    };
+    login_button.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+    on_click.forget();
+}
@@ -1,222 +1,534 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use futures::stream::FuturesUnordered;
-use web_sys::{Document, HtmlDivElement, Window, HtmlImageElement, HtmlProgressElement, HtmlLabelElement, HtmlInputElement, HtmlParagraphElement, HtmlUListElement, HtmlLiElement};
+use std::future::Future;
+use std::rc::Rc;
+use chrono::Duration;
+use web_sys::{Document, Element, Event, HtmlAnchorElement, HtmlButtonElement, HtmlDivElement, Window, HtmlParagraphElement, HtmlSpanElement, HtmlUListElement, HtmlLiElement};
 use wasm_bindgen_futures::spawn_local;
 use wasm_bindgen::JsCast;
-use futures::StreamExt;
 use surf::Client;
 use wasm_bindgen::closure::Closure;
-use gh_client::{GHClient, GHRepository, GHUser};
-
-
-/// Group repositories by language and return counts for every language.
-fn language_count(repositories: Vec<GHRepository>) -> HashMap<String, usize> {
-    let mut map: HashMap<String, usize> = HashMap::new();
-    for repo in repositories {
-        if let Some(language) = &repo.language {
-            if map.contains_key(language) {
-                *map.get_mut(language).unwrap() += 1;
-            } else {
-                map.insert(language.clone(), 1);
-            }
-        }
+use gh_client::crawl::{CancellationToken, CrawlFailure, CrawlProgress};
+use gh_client::snapshot::GHSnapshot;
+use gh_client::stats::language_totals;
+use gh_client::{language_count, GHClient, GHRepository, GHUser};
+
+mod components;
+mod idb;
+mod login;
+mod org_picker;
+mod router;
+
+use components::{append, BarChart, ChartEntry, Component, KeyedList, Matrix, Progress, SearchBar, UserCard, UserCardProps, UserPage, UserPageProps};
+use idb::Database;
+use login::{login, render_logged_out, render_session, Login};
+use org_picker::{pick_organization, recent_orgs, remember_org};
+use router::Route;
+
+
+/// Organization of the current route, else the most recent one for user pages, else the one
+/// the user picks from the recent ones and `suggestions`.
+async fn choose_organization(window: &Window, suggestions: impl Future<Output = Vec<String>>) -> String {
+    let route = Route::current(window);
+    let recent = recent_orgs(window).into_iter().next();
+    match (route, recent) {
+        (Route::Org(org) | Route::Matrix(org), _) => org,
+        (Route::User(_), Some(recent)) => recent,
+        _ => pick_organization(window, suggestions.await).await,
     }
-    map
-    // let mut vec: Vec<(usize, String)> = map.into_iter().map(|(k, v)| (v, k)).collect();
-    // vec.sort_by(|e1, e2| e2.0.cmp(&e1.0));
-    // vec
 }
 
+/// Remember `organization` as recent choice and in the route, once its members loaded. Typos
+/// and unknown organizations thus neither end up in the recent choices nor in the history.
+fn remember_organization(window: &Window, organization: &str) {
+    remember_org(window, organization);
+    if Route::current(window) == Route::Home {
+        Route::Org(organization.to_string()).replace(window);
+    }
+}
 
-fn render_avatars(window: &Window, user_repos: Vec<(GHUser, Vec<GHRepository>)>) {
-    const USER_CONTAINER_ID: &str = "gh-frontend-app-users";
-    const LANGUAGE_INPUT_ID: &str = "gh-frontend-app-language-input";
+/// Show `message` as error on the page.
+fn render_error(window: &Window, message: &str) {
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+    let p: HtmlParagraphElement = append(&document, &root, "p");
+    p.set_attribute("style", "color: red;").unwrap();
+    p.set_text_content(Some(message));
+}
 
+/// Show the page of the current route, and of every later route the user navigates to.
+///
+/// The overview is rendered once and only hidden on other pages, so it keeps its search. The
+/// matrix is rendered on its first visit.
+fn render_routes(window: &Window, organization: &str, user_repos: Vec<(GHUser, Vec<GHRepository>)>) {
     let document: Document = window.document().expect("no document?");
     let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+    let overview: HtmlDivElement = append(&document, &root, "div");
+    let matrix_link: HtmlAnchorElement = append(&document, &overview, "a");
+    matrix_link.set_href(&Route::Matrix(organization.to_string()).to_hash());
+    matrix_link.set_text_content(Some("Members versus languages"));
+    let filter = render_avatars(&document, &overview, user_repos.clone());
+    let detail: HtmlDivElement = append(&document, &root, "div");
+    let page: RefCell<Option<UserPage>> = RefCell::new(None);
+    let matrix: HtmlDivElement = append(&document, &root, "div");
+    let matrix_view: RefCell<Option<Matrix>> = RefCell::new(None);
 
-    let user_languages: Vec<(GHUser, HashMap<String, usize>)> = user_repos.into_iter().map(
-        |(user, repos)| {
-            (user, language_count(repos))
+    let organization = organization.to_string();
+    let overview_route = Route::Org(organization.clone());
+    let show = move |window: &Window| {
+        let route = Route::current(window);
+        log::debug!("showing {route:?}");
+        match route {
+            Route::User(login) => {
+                overview.set_hidden(true);
+                detail.set_hidden(false);
+                matrix.set_hidden(true);
+                let Some((user, repos)) = user_repos.iter().find(|(user, _)| user.login.eq_ignore_ascii_case(&login)) else {
+                    detail.set_text_content(Some(&format!("{login} is not a member of {organization}.")));
+                    page.take();
+                    return;
+                };
+                let props = UserPageProps { org: organization.clone(), user: user.clone(), repos: repos.clone() };
+                let mut page = page.borrow_mut();
+                match page.as_mut() {
+                    Some(page) => page.update(&document, &props),
+                    None => {
+                        detail.set_text_content(None);
+                        let created = UserPage::create(&document, &props);
+                        detail.append_child(created.root()).unwrap();
+                        *page = Some(created);
+                    }
+                }
+                window.scroll_to_with_x_and_y(0.0, 0.0);
+            }
+            // the data of another organization is loaded from scratch
+            Route::Org(org) | Route::Matrix(org) if !org.eq_ignore_ascii_case(&organization) => window.location().reload().unwrap(),
+            Route::Matrix(_) => {
+                overview.set_hidden(true);
+                detail.set_hidden(true);
+                matrix.set_hidden(false);
+                let mut matrix_view = matrix_view.borrow_mut();
+                if matrix_view.is_none() {
+                    let created = Matrix::new(&document, &organization, &user_repos);
+                    matrix.append_child(created.root()).unwrap();
+                    *matrix_view = Some(created);
+                }
+            }
+            Route::Org(_) | Route::Home => {
+                overview.set_hidden(false);
+                detail.set_hidden(true);
+                matrix.set_hidden(true);
+            }
         }
-    ).collect();
+    };
+    // clicks on a language of any chart filter the overview by it
+    let on_click = {
+        let window = window.clone();
+        Closure::<dyn Fn(_)>::new(move |event: Event| {
+            let Some(target) = event.target().and_then(|target| target.dyn_into::<Element>().ok()) else { return };
+            let Some(language) = target.closest("[data-language]").ok().flatten().and_then(|chart| chart.get_attribute("data-language")) else {
+                return;
+            };
+            filter(&language);
+            window.location().set_hash(&overview_route.to_hash()).unwrap();
+        })
+    };
+    root.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+    on_click.forget();
 
-    let header: HtmlDivElement = root.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
-    header.set_text_content(Some("Search for language:"));
+    show(window);
+    let on_hash_change = {
+        let window = window.clone();
+        Closure::<dyn Fn()>::new(move || show(&window))
+    };
+    window.add_event_listener_with_callback("hashchange", on_hash_change.as_ref().unchecked_ref()).unwrap();
+    on_hash_change.forget();
+}
 
-    let language: HtmlInputElement = header.append_child(&document.create_element("input").unwrap()).unwrap().unchecked_into();
-    language.set_attribute("type", "text").unwrap();
-    language.set_attribute("size", "20").unwrap();
-    language.set_id(LANGUAGE_INPUT_ID);
+/// Number of languages in the organization-wide chart.
+const CHART_LANGUAGES: usize = 15;
 
-    {
-        let user_languages = user_languages.clone();
-        let document = document.clone();
-        let a = Closure::<dyn Fn(_)>::new(
-            move |event: web_sys::InputEvent| {
-                log::debug!("event: {:?}", event);
-                let input: HtmlInputElement = document.get_element_by_id(LANGUAGE_INPUT_ID).unwrap().unchecked_into();
-                let search = input.value();
-                log::debug!("input-value: {search}");
-
-                let mut tmp = user_languages.iter().filter_map(
-                    |(user, languages)| {
-                        for (language, count) in languages.iter() {
-                            if language.to_ascii_lowercase().contains(&search.to_ascii_lowercase()) {
-                                return Some((count.clone(), user.clone(), languages.clone()));
-                            }
-                        }
-                        return None;
-                    }).collect::<Vec<_>>();
+/// A chart of the most used languages and cards of all users, with a search bar filtering them
+/// by language.
+///
+/// Returns the filter, to filter by a language clicked elsewhere.
+fn render_avatars(document: &Document, parent: &HtmlDivElement, user_repos: Vec<(GHUser, Vec<GHRepository>)>) -> Rc<dyn Fn(&str)> {
+    let mut totals = language_totals(&user_repos);
+    // the chart shows members, ties keep the order by repositories
+    totals.sort_by_key(|total| std::cmp::Reverse(total.users));
+    let totals: Vec<ChartEntry> = totals
+        .into_iter()
+        .take(CHART_LANGUAGES)
+        .map(|total| ChartEntry {
+            tooltip: format!("{}: {} members, {} repositories", total.language, total.users, total.repositories),
+            language: total.language,
+            value: total.users,
+        })
+        .collect();
+    append::<Element>(document, parent, "h2").set_text_content(Some("Members per language"));
+    parent.append_child(BarChart::create(document, &totals).root()).unwrap();
 
-                tmp.sort_by(|e1, e2| e2.0.cmp(&e1.0));
+    let user_languages: Vec<UserCardProps> = user_repos
+        .into_iter()
+        .map(|(user, repos)| {
+            let mut languages: Vec<(String, usize)> = language_count(&repos).into_iter().collect();
+            languages.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+            UserCardProps { user, languages }
+        })
+        .collect();
 
-                // drop the count that was only needed for sorting and pass it to rendering
-                let tmp = tmp.into_iter().map(|(count, user, languages)| (user, languages)).collect();
-                re_render(tmp);
-            }
-        );
-        language.add_event_listener_with_callback("input", a.as_ref().unchecked_ref()).unwrap();
-        // Avoid dangling closure. Essentially, we tell rust not to clean up the
-        // closure once this method returns (i.e. scope ends).
-        // See also: https://rustwasm.github.io/wasm-bindgen/examples/closures.html
-        a.forget();
-    }
+    let cards: Rc<RefCell<KeyedList<UserCard>>> = Rc::new(RefCell::new(KeyedList::new(document.create_element("div").unwrap())));
+    let on_search = {
+        let (document, cards, user_languages) = (document.clone(), cards.clone(), user_languages.clone());
+        // typed searches match parts of language names, a clicked language only matches itself,
+        // e.g. clicking `C` does not show `CSS`
+        move |search: String, exact: bool| {
+            log::debug!("input-value: {search}");
+            let search = search.to_ascii_lowercase();
+            let matches = |language: &str| match exact {
+                true => language.to_ascii_lowercase() == search,
+                false => language.to_ascii_lowercase().contains(&search),
+            };
+            // users with a matching language, the ones with the most repositories in it first
+            let mut matching: Vec<(usize, &UserCardProps)> = user_languages
+                .iter()
+                .filter_map(|props| {
+                    let count = props.languages.iter().find(|(language, _)| matches(language))?.1;
+                    Some((count, props))
+                })
+                .collect();
+            matching.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+            let items = matching.into_iter().map(|(_, props)| (props.user.login.clone(), props.clone())).collect();
+            cards.borrow_mut().update(&document, items);
+        }
+    };
+    let on_search: Rc<dyn Fn(String, bool)> = Rc::new(on_search);
+    let search = {
+        let on_search = on_search.clone();
+        Rc::new(SearchBar::new(document, "Search for language:", move |search| on_search(search, false)))
+    };
+    parent.append_child(search.root()).unwrap();
+    parent.append_child(cards.borrow().container()).unwrap();
+    let items = user_languages.into_iter().map(|props| (props.user.login.clone(), props)).collect();
+    cards.borrow_mut().update(document, items);
 
-    re_render(user_languages);
+    Rc::new(move |language: &str| {
+        search.set_value(language);
+        on_search(language.to_string(), true);
+    })
+}
 
-    fn re_render(user_languages: Vec<(GHUser, HashMap<String, usize>)>) {
-        log::debug!("rendering");
-        let window: Window = web_sys::window().expect("no window?");
-        let document: Document = window.document().expect("no document?");
-        let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+/// Crawl `organization`, resuming an unfinished crawl of it kept in `database`.
+///
+/// Fails if the members of the organization cannot be fetched.
+async fn fetch_user_repos(window: &Window, database: Option<&Database>, token: &str, organization: &str) -> anyhow::Result<CrawlProgress> {
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+
+    let client = GHClient::new(Client::new(), Some(token.to_string()));
 
-        if let Some(user_containers) = document.get_element_by_id(USER_CONTAINER_ID) {
-            root.remove_child(&user_containers).unwrap();
-            log::debug!("Removed old content")
-        };
-
-        let user_containers: HtmlDivElement = root.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
-        user_containers.set_id(USER_CONTAINER_ID);
-
-        for (user, languages) in user_languages {
-            let user_container: HtmlDivElement = user_containers.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
-            let name_p: HtmlParagraphElement = user_container.append_child(&document.create_element("h1").unwrap()).unwrap().unchecked_into();
-            name_p.set_text_content(Some(&user.login));
-
-            let avatar_and_languages_container: HtmlParagraphElement = user_container.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
-            avatar_and_languages_container.set_attribute("style", "display: flex; align-items: center;").unwrap();
-            let img: HtmlImageElement = avatar_and_languages_container.append_child(&document.create_element("img").unwrap()).unwrap().unchecked_into();
-            img.set_attribute("src", &user.avatar_url).unwrap();
-            img.set_attribute("alt", &user.login).unwrap();
-            img.set_attribute("width", "200").unwrap();
-            img.set_attribute("height", "200").unwrap();
-            let languages_p: HtmlParagraphElement = avatar_and_languages_container.append_child(&document.create_element("p").unwrap()).unwrap().unchecked_into();
-            let list: HtmlUListElement = languages_p.append_child(&document.create_element("ul").unwrap()).unwrap().unchecked_into();
-            for (lang, count) in languages {
-                let i: HtmlLiElement = list.append_child(&document.create_element("li").unwrap()).unwrap().unchecked_into();
-                i.set_text_content(Some(&format!("{lang} ({count})")));
+    // warn about tokens that will only see the public members of the organization
+    match client.check_org_access(organization).await {
+        Ok(access) => {
+            for warning in access.warnings() {
+                let p: HtmlParagraphElement = root.append_child(&document.create_element("p").unwrap()).unwrap().unchecked_into();
+                p.set_attribute("style", "color: darkorange;").unwrap();
+                p.set_text_content(Some(&format!("Warning: {warning}")));
             }
         }
+        Err(msg) => log::warn!("Failed to check token access for {organization}: {msg}"),
     }
+
+    // resume an unfinished crawl of the same organization
+    let resumed = match database {
+        Some(database) => database.load_crawl(organization).await.unwrap_or_else(|msg| {
+            log::warn!("Discarding unreadable crawl progress: {msg}");
+            None
+        }),
+        None => None,
+    };
+    if let Some(resumed) = &resumed {
+        log::info!("Resuming crawl of {organization} with {} of {} members done", resumed.done.len(), resumed.total());
+    }
+    crawl_user_repos(&document, database, &client, resumed.unwrap_or_else(|| CrawlProgress::new(organization))).await
 }
 
-async fn fetch_user_repos(window: &Window, token: &str, organization: &str) -> Vec<(GHUser, Vec<GHRepository>)> {
-    let document: Document = window.document().expect("no document?");
+/// Continue `crawl` with a progress bar, storing its progress in `database` if there is one.
+async fn crawl_user_repos(document: &Document, database: Option<&Database>, client: &GHClient, crawl: CrawlProgress) -> anyhow::Result<CrawlProgress> {
     let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+    let cancel = CancellationToken::default();
+    let progress = {
+        let cancel = cancel.clone();
+        Progress::new(document, "Fetching Users", move || cancel.cancel())
+    };
+    root.append_child(progress.root()).unwrap();
 
-    let client = GHClient::new(Client::new(), Some(token.to_string()));
-
-    let label: HtmlLabelElement = root.append_child(&document.create_element("label").unwrap()).unwrap().unchecked_into();
-    label.set_attribute("for", "progress").unwrap();
-    label.set_text_content(Some("Fetching Users"));
-    let progress: HtmlProgressElement = root.append_child(&document.create_element("progress").unwrap()).unwrap().unchecked_into();
-    progress.set_attribute("id", "progress").unwrap();
-
-    let users = client.get_org_members(organization).await.unwrap();
-    let total = users.len();
-    label.set_text_content(Some("Fetching repositories for users:"));
-    progress.set_attribute("value", "0").unwrap();
-    progress.set_attribute("max", &total.to_string()).unwrap();
-
-    // create a stream of (username, repositories) pairs. An item in the stream will become available
-    // once its underlying fetch request is finished.
-    let user_repo_stream: FuturesUnordered<_> = users
-        .into_iter()
-        .map(|user| async {
-            let repos = client.get_user_repositories(&user.login).await;
-            (user, repos)
+    let crawled = client
+        .crawl_org(crawl, &cancel, |crawl| {
+            let (done, total) = (crawl.done.len(), crawl.total());
+            progress.set(&format!("Fetching repositories for users ({done:3}/{total:3}):"), done, total);
+            let Some(database) = database else { return };
+            // transactions run in the order they are started, so the last progress wins
+            let (database, crawl) = (database.clone(), crawl.clone());
+            spawn_local(async move {
+                if let Err(msg) = database.store_crawl(&crawl).await {
+                    log::warn!("Failed to store crawl progress: {msg}");
+                }
+            });
         })
-        .collect();
+        .await;
+    drop(progress);
+    crawled
+}
+
+/// Store a complete crawl as snapshot, replacing its progress.
+async fn store_crawled(database: &Database, crawl: CrawlProgress) -> GHSnapshot {
+    let organization = crawl.org.clone();
+    let snapshot = GHSnapshot::new(&organization, crawl.into_data());
+    log::info!("Storing data in IndexedDB");
+    if let Err(msg) = database.store_snapshot(&snapshot).await {
+        log::error!("Failed to store data in IndexedDB: {msg}");
+    }
+    if let Err(msg) = database.delete_crawl(&snapshot.org).await {
+        log::warn!("Failed to delete finished crawl progress: {msg}");
+    }
+    snapshot
+}
+
+/// Explain in `notice` that `crawl` is incomplete, listing the members whose repositories could
+/// not be fetched, with a button to crawl the pending members again.
+///
+/// Once the crawl is complete, the page is reloaded to show the stored data. Without `database`
+/// nothing survives a reload, so there is no button.
+fn render_incomplete(window: &Window, database: Option<&Database>, token: &str, notice: &HtmlDivElement, crawl: &CrawlProgress) {
+    let document: Document = window.document().expect("no document?");
+    notice.set_inner_html("");
+    let p: HtmlParagraphElement = append(&document, notice, "p");
+    p.set_attribute("style", "color: darkorange;").unwrap();
+    p.set_text_content(Some(&format!("Showing {} of {} members.", crawl.done.len(), crawl.total())));
 
-    // await all the items in the stream and store them in the return hashmap.
-    let user_repos = user_repo_stream
-        .filter_map(
-            |(user, repos)| async {
-                match repos {
-                    Ok(x) => {
-                        let new = progress.value() + 1.;
-                        progress.set_value(new);
-                        label.set_text_content(Some(&format!("Fetching repositories for users ({new:3.0}/{total:3.0}):")));
-                        Some((user, x))
+    if !crawl.failures.is_empty() {
+        let list: HtmlUListElement = append(&document, notice, "ul");
+        for failure in &crawl.failures {
+            let item: HtmlLiElement = append(&document, &list, "li");
+            let hint = if failure.retriable { "" } else { ", retrying will not help" };
+            item.set_text_content(Some(&format!("{}: {:?}{hint} ({})", failure.login, failure.kind, failure.message)));
+        }
+    }
+
+    let Some(database) = database else { return };
+    let retry: HtmlButtonElement = append(&document, notice, "button");
+    retry.set_text_content(Some(&match crawl.failures.len() {
+        0 => format!("Fetch the remaining {} members", crawl.total() - crawl.done.len()),
+        failed => format!("Retry {failed} failed members"),
+    }));
+    let on_click = {
+        let (window, database, token, notice, crawl) = (window.clone(), database.clone(), token.to_string(), notice.clone(), crawl.clone());
+        let retry = retry.clone();
+        Closure::<dyn Fn()>::new(move || {
+            let (window, database, token, notice, crawl) = (window.clone(), database.clone(), token.clone(), notice.clone(), crawl.clone());
+            retry.set_disabled(true);
+            spawn_local(async move {
+                let document: Document = window.document().expect("no document?");
+                let client = GHClient::new(Client::new(), Some(token.clone()));
+                match crawl_user_repos(&document, Some(&database), &client, crawl.clone()).await {
+                    Ok(crawled) if crawled.is_complete() => {
+                        store_crawled(&database, crawled).await;
+                        window.location().reload().unwrap();
                     }
+                    Ok(crawled) => render_incomplete(&window, Some(&database), &token, &notice, &crawled),
                     Err(msg) => {
-                        log::warn!("Failed to fetch repos for {user:?}: {msg}");
-                        None
+                        log::error!("Failed to fetch members of {}: {msg}", crawl.org);
+                        render_incomplete(&window, Some(&database), &token, &notice, &crawl);
+                        let p: HtmlParagraphElement = append(&document, &notice, "p");
+                        p.set_attribute("style", "color: red;").unwrap();
+                        p.set_text_content(Some(&format!("Failed to fetch the members: {msg}")));
                     }
                 }
-            }
-        ).collect()
-        .await;
-    root.remove_child(&label).unwrap();
-    root.remove_child(&progress).unwrap();
-    user_repos
+            });
+        })
+    };
+    retry.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+    on_click.forget();
 }
 
-/// Try to load the GH-API token from the browsers local storage.
+/// Base url of a gh-server backend (e.g. `http://localhost:3000`), if configured in local storage.
+///
+/// gh-server listens on port 3000 by default, apart from the port 8080 of `trunk serve`.
 ///
-/// If the token is not yet in local storage, then an input dialog asking the
-/// user to provide the it will be displayed, and the token will be saved in local storage.
-fn get_api_token(window: &Window) -> String {
-    const TOKEN_STORAGE_KEY: &str = "gh-frontend-app-api-token";
+/// With a backend, data is served by it instead of GitHub and the browser never sees a GH-API token.
+fn get_backend_url(window: &Window) -> Option<String> {
+    const BACKEND_URL_STORAGE_KEY: &str = "gh-frontend-app-backend-url";
     let local_storage = window.local_storage().unwrap().unwrap();
-    let token = local_storage.get(TOKEN_STORAGE_KEY).unwrap();
-    match token {
-        None => {
-            // fixme: handle case where used clicks cancel.
-            let token = window.prompt_with_message("Please provide GH-API token:").unwrap().unwrap();
-            local_storage.set(TOKEN_STORAGE_KEY, &token).expect("Failed to store token in local storage.");
-            token
+    local_storage.get(BACKEND_URL_STORAGE_KEY).unwrap().map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Load the User/Repositories of an organization synced by the gh-server backend.
+async fn fetch_backend_user_repos(backend: &str, organization: &str) -> anyhow::Result<Vec<(GHUser, Vec<GHRepository>)>> {
+    log::info!("fetching {organization} from backend {backend}");
+    let mut response = surf::get(format!("{backend}/orgs/{organization}/members")).await.map_err(|msg| anyhow::anyhow!("{msg}"))?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("the backend answered with {}", response.status()));
+    }
+    response.body_json().await.map_err(|msg| anyhow::anyhow!("{msg}"))
+}
+
+/// Organizations synced by the gh-server backend.
+async fn fetch_backend_orgs(backend: &str) -> Vec<String> {
+    match surf::get(format!("{backend}/status")).recv_json::<HashMap<String, serde_json::Value>>().await {
+        Ok(status) => status.into_keys().collect(),
+        Err(msg) => {
+            log::warn!("Failed to fetch the organizations of backend {backend}: {msg}");
+            Vec::new()
         }
-        Some(token) => { token }
     }
 }
 
-/// Try to load the User/Repositories from browser local storage.
-///
-/// If local storage does not contain the data, it will be fetched from GH and stored in local storage.
-async fn get_user_repos(window: &Window, token: &str, organization: &str) -> Vec<(GHUser, Vec<GHRepository>)> {
-    const USER_REPOSITORIES_STORAGE_KEY: &str = "gh-frontend-app-user-repositories";
+/// Organizations of the token's user.
+async fn fetch_user_orgs(token: &str) -> Vec<String> {
+    let client = GHClient::new(Client::new(), Some(token.to_string()));
+    match client.get_user_orgs().await {
+        Ok(orgs) => orgs.into_iter().map(|org| org.login).collect(),
+        Err(msg) => {
+            log::warn!("Failed to fetch the organizations of the token's user: {msg}");
+            Vec::new()
+        }
+    }
+}
+
+/// Local storage key of the number of hours cached data is shown without refreshing it.
+const CACHE_TTL_STORAGE_KEY: &str = "gh-frontend-app-cache-ttl-hours";
+const DEFAULT_CACHE_TTL_HOURS: i64 = 24;
+
+fn cache_ttl(window: &Window) -> Duration {
     let local_storage = window.local_storage().unwrap().unwrap();
-    let data = local_storage.get(USER_REPOSITORIES_STORAGE_KEY).unwrap();
-    match data {
-        None => {
-            let data = fetch_user_repos(window, token, organization).await;
-            let json = serde_json::to_string(&data).unwrap();
-            log::info!("Storing data in local storage");
-            local_storage.set(USER_REPOSITORIES_STORAGE_KEY, &json).unwrap();
-            data
+    let hours = local_storage
+        .get(CACHE_TTL_STORAGE_KEY)
+        .unwrap()
+        .and_then(|hours| hours.trim().parse().ok())
+        .unwrap_or(DEFAULT_CACHE_TTL_HOURS);
+    Duration::hours(hours)
+}
+
+/// Show when the data was fetched, with a button to refresh it in the background.
+///
+/// Expired data is refreshed right away.
+fn render_freshness(window: &Window, database: &Database, token: &str, snapshot: &GHSnapshot) {
+    const FRESHNESS_ID: &str = "gh-frontend-app-freshness";
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+
+    let freshness: HtmlDivElement = root.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
+    freshness.set_id(FRESHNESS_ID);
+    let status: HtmlSpanElement = freshness.append_child(&document.create_element("span").unwrap()).unwrap().unchecked_into();
+    status.set_text_content(Some(&format!("Data as of {} ", snapshot.fetched_at.format("%Y-%m-%d %H:%M UTC"))));
+    let refresh: HtmlButtonElement = freshness.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+    refresh.set_text_content(Some("Refresh"));
+
+    let start = {
+        let (window, database, token, organization) = (window.clone(), database.clone(), token.to_string(), snapshot.org.clone());
+        let (status, refresh) = (status.clone(), refresh.clone());
+        Rc::new(move || {
+            let (window, database, token, organization) = (window.clone(), database.clone(), token.clone(), organization.clone());
+            let (status, refresh) = (status.clone(), refresh.clone());
+            refresh.set_disabled(true);
+            status.set_text_content(Some("Refreshing data in the background... "));
+            spawn_local(async move {
+                match refresh_user_repos(&database, &token, &organization).await {
+                    Ok((snapshot, failures)) if failures.is_empty() => {
+                        status.set_text_content(Some(&format!(
+                            "Refreshed data as of {} is available ",
+                            snapshot.fetched_at.format("%Y-%m-%d %H:%M UTC")
+                        )));
+                        // the refreshed data is in the database, where the reload picks it up
+                        refresh.set_text_content(Some("Show"));
+                        let on_click = Closure::<dyn Fn()>::new(move || window.location().reload().unwrap());
+                        refresh.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+                        on_click.forget();
+                    }
+                    Ok((_, failures)) => {
+                        let members: Vec<String> = failures.iter().map(CrawlFailure::to_string).collect();
+                        status.set_text_content(Some(&format!(
+                            "Refreshed data is available, but without current repositories of {} ",
+                            members.join(", ")
+                        )));
+                        refresh.set_text_content(Some("Show"));
+                        let on_click = Closure::<dyn Fn()>::new(move || window.location().reload().unwrap());
+                        refresh.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+                        on_click.forget();
+                    }
+                    Err(msg) => {
+                        log::error!("Failed to refresh {organization}: {msg}");
+                        status.set_text_content(Some(&format!("Refreshing failed: {msg} ")));
+                    }
+                }
+                refresh.set_disabled(false);
+            });
+        })
+    };
+    {
+        let start = start.clone();
+        let on_click = Closure::<dyn Fn()>::new(move || start());
+        refresh.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+        on_click.forget();
+    }
+    if snapshot.is_expired(cache_ttl(window)) {
+        log::info!("Cached data of {} from {} expired", snapshot.org, snapshot.fetched_at);
+        start();
+    }
+}
+
+/// Refresh the cached data of `organization`, fetching only the repositories of members that
+/// changed since, along with the members that failed.
+async fn refresh_user_repos(database: &Database, token: &str, organization: &str) -> anyhow::Result<(GHSnapshot, Vec<CrawlFailure>)> {
+    let cached = database.load_snapshot(organization).await?.unwrap_or_else(|| GHSnapshot::new(organization, Vec::new()));
+    let client = GHClient::new(Client::new(), Some(token.to_string()));
+    let (snapshot, failures) = client.refresh_snapshot(&cached).await?;
+    database.store_snapshot(&snapshot).await?;
+    Ok((snapshot, failures))
+}
+
+/// Try to load the User/Repositories from the browser's IndexedDB.
+///
+/// If the database does not contain the data, it will be fetched from GH and stored in the database.
+/// Cached data older than the [cache_ttl] is shown while being refreshed in the background.
+/// Without `database` the data is fetched on every visit. Fails if the members of an
+/// organization that is not cached cannot be fetched.
+async fn get_user_repos(window: &Window, database: Option<&Database>, token: &str, organization: &str) -> anyhow::Result<Vec<(GHUser, Vec<GHRepository>)>> {
+    if let Some(database) = database {
+        let cached = database.load_snapshot(organization).await.unwrap_or_else(|msg| {
+            log::warn!("Discarding unreadable data from IndexedDB: {msg}");
+            None
+        });
+        if let Some(snapshot) = cached {
+            render_freshness(window, database, token, &snapshot);
+            return Ok(snapshot.data);
         }
-        Some(data) => {
-            log::debug!("Deserializing data from local storage");
-            serde_json::from_str(&data).unwrap()
+    }
+
+    let crawl = fetch_user_repos(window, database, token, organization).await?;
+    if !crawl.is_complete() {
+        let document: Document = window.document().expect("no document?");
+        let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+        let notice: HtmlDivElement = append(&document, &root, "div");
+        render_incomplete(window, database, token, &notice, &crawl);
+        return Ok(crawl.into_data());
+    }
+    match database {
+        Some(database) => {
+            let snapshot = store_crawled(database, crawl).await;
+            render_freshness(window, database, token, &snapshot);
+            Ok(snapshot.data)
         }
+        None => Ok(crawl.into_data()),
     }
 }
 
 // #[wasm_bindgen] done by trunk :)
 fn main() {
     wasm_logger::init(wasm_logger::Config::default());
+    // spans of gh-client requests, the log records of the app itself still go through wasm_logger
+    tracing_wasm::set_as_global_default();
     log::info!("Hello, world!");
     let window: Window = web_sys::window().expect("no window?");
     log::info!("got window {:?}", &window);
@@ -231,13 +543,58 @@ fn main() {
         async {
             let window: Window = web_sys::window().expect("no window?");
 
-            let token = get_api_token(&window);
-            let user_repos = get_user_repos(&window, &token, "codecentric").await;
-
+            let (organization, user_repos) = match get_backend_url(&window) {
+                Some(backend) => {
+                    let organization = choose_organization(&window, fetch_backend_orgs(&backend)).await;
+                    match fetch_backend_user_repos(&backend, &organization).await {
+                        Ok(user_repos) => (organization, user_repos),
+                        Err(msg) => {
+                            log::error!("Failed to fetch {organization} from backend {backend}: {msg}");
+                            render_error(&window, &format!("Failed to fetch {organization} from the backend {backend}: {msg}"));
+                            return;
+                        }
+                    }
+                }
+                None => {
+                    let Some(Login { token, info }) = login(&window).await else {
+                        render_logged_out(&window);
+                        return;
+                    };
+                    render_session(&window, &info);
+                    // without a database the data is still shown, just not cached
+                    let database = match Database::open(&window).await {
+                        Ok(database) => Some(database),
+                        Err(msg) => {
+                            log::error!("{msg}");
+                            let document: Document = window.document().expect("no document?");
+                            let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+                            let p: HtmlParagraphElement = append(&document, &root, "p");
+                            p.set_attribute("style", "color: darkorange;").unwrap();
+                            p.set_text_content(Some(&format!("Cannot store data in this browser, it is fetched again on every visit: {msg}")));
+                            None
+                        }
+                    };
+                    if let Some(database) = &database {
+                        if let Err(msg) = database.migrate_local_storage(&window).await {
+                            log::warn!("Failed to migrate data from local storage: {msg}");
+                        }
+                    }
+                    let organization = choose_organization(&window, fetch_user_orgs(&token)).await;
+                    match get_user_repos(&window, database.as_ref(), &token, &organization).await {
+                        Ok(user_repos) => (organization, user_repos),
+                        Err(msg) => {
+                            log::error!("Failed to fetch members of {organization}: {msg}");
+                            render_error(&window, &format!("Failed to fetch the members of {organization}: {msg}"));
+                            return;
+                        }
+                    }
+                }
+            };
+            remember_organization(&window, &organization);
 
             // log::debug!("repos: {user_repos:?}");
             // let repos = get_user_repositories(&token, &users[0].login).await;
-            render_avatars(&window, user_repos);
+            render_routes(&window, &organization, user_repos);
         }
     );
 }
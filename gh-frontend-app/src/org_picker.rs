@@ -0,0 +1,88 @@
+//! Selection of the organization to show, from recent choices, suggestions or free text.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures::channel::oneshot;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Event, HtmlButtonElement, HtmlDivElement, HtmlFormElement, HtmlInputElement, Window};
+
+const RECENT_ORGS_STORAGE_KEY: &str = "gh-frontend-app-recent-orgs";
+const MAX_RECENT_ORGS: usize = 5;
+const PICKER_ID: &str = "gh-frontend-app-org-picker";
+
+/// Recently chosen organizations, the most recent first.
+pub fn recent_orgs(window: &Window) -> Vec<String> {
+    let local_storage = window.local_storage().unwrap().unwrap();
+    local_storage
+        .get(RECENT_ORGS_STORAGE_KEY)
+        .unwrap()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Put `org` first in the recent choices, once its members loaded.
+pub fn remember_org(window: &Window, org: &str) {
+    let mut recent = recent_orgs(window);
+    recent.retain(|recent| !recent.eq_ignore_ascii_case(org));
+    recent.insert(0, org.to_string());
+    recent.truncate(MAX_RECENT_ORGS);
+    let local_storage = window.local_storage().unwrap().unwrap();
+    local_storage.set(RECENT_ORGS_STORAGE_KEY, &serde_json::to_string(&recent).unwrap()).unwrap();
+}
+
+/// Let the user choose an organization, offering the recent choices followed by `suggestions`.
+pub async fn pick_organization(window: &Window, suggestions: Vec<String>) -> String {
+    let document: Document = window.document().expect("no document?");
+    let root: HtmlDivElement = document.get_element_by_id("root").unwrap().unchecked_into();
+
+    let picker: HtmlDivElement = root.append_child(&document.create_element("div").unwrap()).unwrap().unchecked_into();
+    picker.set_id(PICKER_ID);
+    picker.append_child(&document.create_element("h2").unwrap()).unwrap().set_text_content(Some("Choose an organization"));
+
+    // the first choice completes the picker, later clicks are ignored
+    let (sender, receiver) = oneshot::channel::<String>();
+    let sender = RefCell::new(Some(sender));
+    let choose: Rc<dyn Fn(String)> = Rc::new(move |org: String| {
+        if let Some(sender) = sender.borrow_mut().take() {
+            sender.send(org).ok();
+        }
+    });
+
+    let mut orgs = recent_orgs(window);
+    for suggestion in suggestions {
+        if !orgs.iter().any(|org| org.eq_ignore_ascii_case(&suggestion)) {
+            orgs.push(suggestion);
+        }
+    }
+    for org in orgs {
+        let button: HtmlButtonElement = picker.append_child(&document.create_element("button").unwrap()).unwrap().unchecked_into();
+        button.set_text_content(Some(&org));
+        let choose = choose.clone();
+        let on_click = Closure::<dyn Fn()>::new(move || choose(org.clone()));
+        button.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref()).unwrap();
+        on_click.forget();
+    }
+
+    let form: HtmlFormElement = picker.append_child(&document.create_element("form").unwrap()).unwrap().unchecked_into();
+    let input: HtmlInputElement = form.append_child(&document.create_element("input").unwrap()).unwrap().unchecked_into();
+    input.set_attribute("type", "text").unwrap();
+    input.set_attribute("placeholder", "organization").unwrap();
+    form.append_child(&document.create_element("button").unwrap()).unwrap().set_text_content(Some("Show"));
+    {
+        let input = input.clone();
+        let on_submit = Closure::<dyn Fn(_)>::new(move |event: Event| {
+            event.prevent_default();
+            let org = input.value().trim().to_string();
+            if !org.is_empty() {
+                choose(org);
+            }
+        });
+        form.add_event_listener_with_callback("submit", on_submit.as_ref().unchecked_ref()).unwrap();
+        on_submit.forget();
+    }
+
+    let org = receiver.await.expect("picker removed without a choice");
+    root.remove_child(&picker).unwrap();
+    org
+}
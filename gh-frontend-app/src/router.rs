@@ -0,0 +1,89 @@
+//! Hash based routing, so that pages can be linked to and browser back/forward works.
+//!
+//! | hash                 | page                                        |
+//! |----------------------|---------------------------------------------|
+//! | `#/org/{org}`        | overview of all members of an organization  |
+//! | `#/org/{org}/matrix` | members versus languages of an organization |
+//! | `#/user/{login}`     | details of a single member                  |
+use web_sys::Window;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// No or an unknown hash.
+    Home,
+    Org(String),
+    Matrix(String),
+    User(String),
+}
+
+impl Route {
+    pub fn parse(hash: &str) -> Self {
+        let path = hash.trim_start_matches('#').trim_matches('/');
+        match path.split_once('/') {
+            Some(("org", org)) if !org.is_empty() && !org.contains('/') => Route::Org(org.to_string()),
+            Some(("org", org)) => match org.split_once('/') {
+                Some((org, "matrix")) if !org.is_empty() => Route::Matrix(org.to_string()),
+                _ => Route::Home,
+            },
+            Some(("user", login)) if !login.is_empty() && !login.contains('/') => Route::User(login.to_string()),
+            _ => Route::Home,
+        }
+    }
+
+    /// Route of the current location of `window`.
+    pub fn current(window: &Window) -> Self {
+        Self::parse(&window.location().hash().unwrap_or_default())
+    }
+
+    pub fn to_hash(&self) -> String {
+        match self {
+            Route::Home => "#/".to_string(),
+            Route::Org(org) => format!("#/org/{org}"),
+            Route::Matrix(org) => format!("#/org/{org}/matrix"),
+            Route::User(login) => format!("#/user/{login}"),
+        }
+    }
+
+    /// Show `self` in the address bar without adding a history entry.
+    pub fn replace(&self, window: &Window) {
+        let history = window.history().unwrap();
+        history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&self.to_hash())).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::Route;
+
+    #[wasm_bindgen_test]
+    fn test_parse() {
+        assert_eq!(Route::Home, Route::parse(""));
+        assert_eq!(Route::Home, Route::parse("#/"));
+        assert_eq!(Route::Org("x".into()), Route::parse("#/org/x"));
+        assert_eq!(Route::Matrix("x".into()), Route::parse("#/org/x/matrix"));
+        assert_eq!(Route::User("x".into()), Route::parse("#/user/x"));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_parse_trailing_slash() {
+        assert_eq!(Route::Org("x".into()), Route::parse("#/org/x/"));
+        assert_eq!(Route::Matrix("x".into()), Route::parse("#/org/x/matrix/"));
+        assert_eq!(Route::User("x".into()), Route::parse("#/user/x/"));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_parse_junk() {
+        for hash in ["#", "#/org", "#/org/", "#/org//matrix", "#/org/x/y", "#/org/x/matrix/y", "#/user", "#/user/x/y", "#/other/x", "junk"] {
+            assert_eq!(Route::Home, Route::parse(hash), "{hash}");
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_round_trip() {
+        for route in [Route::Home, Route::Org("x".into()), Route::Matrix("x".into()), Route::User("x".into())] {
+            assert_eq!(route, Route::parse(&route.to_hash()));
+        }
+    }
+}
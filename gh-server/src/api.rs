@@ -0,0 +1,175 @@
+//! JSON API serving the synced organization data.
+//!
+//! | route                       | response                                                   |
+//! |-----------------------------|------------------------------------------------------------|
+//! | `GET /orgs/{org}/members`   | `[[user, [repository, ...]], ...]`, as cached by the frontend |
+//! | `GET /orgs/{org}/languages` | repository and user counts per language                     |
+//! | `GET /search?lang=&org=`    | members with repositories in `lang`, optionally of one `org` |
+//! | `GET /metrics`              | GH-API request and sync metrics in Prometheus text format   |
+//! | `GET /status`               | last sync success and failure of every organization         |
+//! | `POST /webhook`             | applies a GitHub webhook delivery, see [crate::webhook]     |
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_std::sync::RwLock;
+use gh_client::metrics::Metrics;
+use gh_client::snapshot::GHSnapshot;
+use gh_client::stats::{language_totals, users_by_language};
+use serde::{Deserialize, Serialize};
+use tide::http::headers::HeaderValue;
+use tide::security::{CorsMiddleware, Origin};
+use tide::{Body, Request, Response, StatusCode};
+
+use crate::sync::{SnapshotHistory, SyncStatus};
+use crate::webhook;
+
+/// Latest snapshot of every synced organization.
+#[derive(Clone, Default)]
+pub struct State {
+    pub snapshots: Arc<RwLock<HashMap<String, GHSnapshot>>>,
+    pub status: Arc<RwLock<HashMap<String, SyncStatus>>>,
+    /// Metrics of the client used for syncing.
+    pub metrics: Arc<Metrics>,
+    /// Secret to verify webhook deliveries with, webhooks are disabled without it.
+    pub webhook_secret: Option<String>,
+    /// Keeps the served snapshots of syncs and webhook deliveries, if configured.
+    pub history: Option<Arc<SnapshotHistory>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    lang: String,
+    org: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchHit {
+    pub org: String,
+    pub login: String,
+    pub repositories: usize,
+}
+
+pub fn app(state: State) -> tide::Server<State> {
+    let mut app = tide::with_state(state);
+    // the frontend is served from a different origin
+    app.with(
+        CorsMiddleware::new()
+            .allow_methods("GET".parse::<HeaderValue>().unwrap())
+            .allow_origin(Origin::from("*")),
+    );
+    app.at("/orgs/:org/members").get(members);
+    app.at("/orgs/:org/languages").get(languages);
+    app.at("/search").get(search);
+    app.at("/status").get(status);
+    app.at("/metrics").get(metrics);
+    app.at("/webhook").post(webhook::receive);
+    app
+}
+
+/// The snapshot of the `:org` parameter, or a 404 if the organization has not been synced.
+async fn snapshot(req: &Request<State>) -> tide::Result<GHSnapshot> {
+    let org = req.param("org")?;
+    req.state()
+        .snapshots
+        .read()
+        .await
+        .get(org)
+        .cloned()
+        .ok_or_else(|| tide::Error::from_str(StatusCode::NotFound, format!("Organization {org} has not been synced")))
+}
+
+async fn members(req: Request<State>) -> tide::Result<Body> {
+    Body::from_json(&snapshot(&req).await?.data)
+}
+
+async fn languages(req: Request<State>) -> tide::Result<Body> {
+    Body::from_json(&language_totals(&snapshot(&req).await?.data))
+}
+
+async fn search(req: Request<State>) -> tide::Result<Body> {
+    let query: SearchQuery = req.query()?;
+    let snapshots = req.state().snapshots.read().await;
+    let mut hits: Vec<SearchHit> = snapshots
+        .iter()
+        .filter(|(org, _)| query.org.as_ref().map(|o| o == *org).unwrap_or(true))
+        .flat_map(|(org, snapshot)| {
+            users_by_language(&snapshot.data, &query.lang)
+                .into_iter()
+                .map(|user| SearchHit { org: org.clone(), login: user.login, repositories: user.repositories })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.repositories.cmp(&a.repositories).then_with(|| a.login.cmp(&b.login)));
+    Body::from_json(&hits)
+}
+
+async fn status(req: Request<State>) -> tide::Result<Body> {
+    Body::from_json(&*req.state().status.read().await)
+}
+
+async fn metrics(req: Request<State>) -> tide::Result {
+    let mut response = Response::new(StatusCode::Ok);
+    response.set_body(req.state().metrics.render());
+    response.set_content_type("text/plain; version=0.0.4");
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gh_client::stats::LanguageTotal;
+    use gh_client::fixtures::{repo, user};
+    use tide::http::{Method, Response, Url};
+
+    async fn get(app: &tide::Server<State>, path: &str) -> Response {
+        let request = tide::http::Request::new(Method::Get, Url::parse(&format!("http://localhost{path}")).unwrap());
+        app.respond(request).await.unwrap()
+    }
+
+    async fn state() -> State {
+        let state = State::default();
+        let data = vec![(user("alice", 1), vec![repo("a", Some("Rust")), repo("b", Some("Rust"))]), (user("bob", 2), vec![repo("c", Some("Go"))])];
+        state.snapshots.write().await.insert("octo".into(), GHSnapshot::new("octo", data));
+        state
+    }
+
+    #[async_std::test]
+    async fn test_languages() {
+        let app = app(state().await);
+        let mut response = get(&app, "/orgs/octo/languages").await;
+        assert_eq!(StatusCode::Ok, response.status());
+        let totals: Vec<LanguageTotal> = response.body_json().await.unwrap();
+        assert_eq!("Rust", totals[0].language);
+        assert_eq!(2, totals[0].repositories);
+    }
+
+    #[async_std::test]
+    async fn test_unknown_org() {
+        let app = app(state().await);
+        assert_eq!(StatusCode::NotFound, get(&app, "/orgs/other/members").await.status());
+    }
+
+    #[async_std::test]
+    async fn test_status() {
+        let state = state().await;
+        state.status.write().await.insert("octo".into(), SyncStatus { members: 2, ..Default::default() });
+        let status: HashMap<String, SyncStatus> = get(&app(state), "/status").await.body_json().await.unwrap();
+        assert_eq!(2, status["octo"].members);
+    }
+
+    #[async_std::test]
+    async fn test_metrics() {
+        let state = state().await;
+        state.metrics.record_sync("octo", true, 1.5);
+        let body = get(&app(state), "/metrics").await.body_string().await.unwrap();
+        assert!(body.contains("gh_sync_runs_total{org=\"octo\",result=\"success\"} 1"));
+    }
+
+    #[async_std::test]
+    async fn test_search() {
+        let app = app(state().await);
+        let hits: Vec<SearchHit> = get(&app, "/search?lang=rust").await.body_json().await.unwrap();
+        assert_eq!(vec![SearchHit { org: "octo".into(), login: "alice".into(), repositories: 2 }], hits);
+        let hits: Vec<SearchHit> = get(&app, "/search?lang=rust&org=other").await.body_json().await.unwrap();
+        assert!(hits.is_empty());
+    }
+}
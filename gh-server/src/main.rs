@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::Parser;
+use cron::Schedule;
+use gh_client::GHClient;
+use surf::Client;
+use tracing_subscriber::EnvFilter;
+use sync::SnapshotHistory;
+
+mod api;
+mod sync;
+mod webhook;
+
+/// Serve the members and languages of GitHub organizations without handing out GH-API tokens.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// GH-API token(s), comma separated. Requests rotate between multiple tokens.
+    #[arg(long, env = "GH_API_TOKEN", value_delimiter = ',', hide_env_values = true)]
+    token: Vec<String>,
+
+    /// Organization(s) to sync, comma separated.
+    #[arg(long, env = "GH_SERVER_ORGS", value_delimiter = ',', required = true)]
+    org: Vec<String>,
+
+    /// Address to listen on, port 3000 by default to not collide with `trunk serve` on 8080.
+    #[arg(long, env = "GH_SERVER_LISTEN", default_value = "127.0.0.1:3000")]
+    listen: String,
+
+    /// When to sync the organizations after the initial sync, as cron expression with seconds.
+    #[arg(long, env = "GH_SERVER_SCHEDULE", default_value = "0 0 * * * *", value_parser = Schedule::from_str)]
+    schedule: Schedule,
+
+    /// Directory to keep the latest snapshots of every organization in, served until the first sync.
+    #[arg(long, env = "GH_SERVER_SNAPSHOT_DIR")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Number of snapshots to keep per organization in the `--snapshot-dir`.
+    #[arg(long, default_value = "10")]
+    keep_snapshots: usize,
+
+    /// Secret of the organization webhooks, enables `POST /webhook` for live updates between syncs.
+    #[arg(long, env = "GH_SERVER_WEBHOOK_SECRET", hide_env_values = true)]
+    webhook_secret: Option<String>,
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env()).init();
+    let args = Args::parse();
+    if args.token.is_empty() {
+        log::warn!("No GH-API token configured, unauthenticated requests are limited to 60 per hour.");
+    }
+
+    let client = Arc::new(GHClient::with_tokens(Client::new(), args.token));
+    let history = args.snapshot_dir.map(|dir| Arc::new(SnapshotHistory::new(dir, args.keep_snapshots)));
+    let state = api::State { webhook_secret: args.webhook_secret, metrics: client.metrics(), history, ..Default::default() };
+    for org in args.org {
+        async_std::task::spawn(sync::sync_on_schedule(client.clone(), state.clone(), org, args.schedule.clone()));
+    }
+
+    log::info!("listening on {}", args.listen);
+    api::app(state).listen(args.listen).await?;
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Scheduled crawling of organizations into the server state.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use gh_client::crawl::{failure_summary, CrawlFailure};
+use gh_client::estimate::recrawl_requests;
+use gh_client::snapshot::GHSnapshot;
+use gh_client::GHClient;
+use serde::{Deserialize, Serialize};
+
+use crate::api::State;
+
+/// Sync state of an organization, served by `GET /status`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct SyncStatus {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Error of the last failed sync.
+    pub last_error: Option<String>,
+    pub next_run: Option<DateTime<Utc>>,
+    /// Number of members in the served snapshot.
+    pub members: usize,
+    /// Members that failed in the last sync, served with their repositories of the sync before.
+    #[serde(default)]
+    pub failures: Vec<CrawlFailure>,
+}
+
+/// Directory keeping the latest snapshots of every organization as `{org}/{fetched_at}.json`.
+pub struct SnapshotHistory {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl SnapshotHistory {
+    /// Keep the last `keep` (at least one) snapshots of every organization in `dir`.
+    pub fn new(dir: impl Into<PathBuf>, keep: usize) -> Self {
+        Self { dir: dir.into(), keep: keep.max(1) }
+    }
+
+    /// Snapshot files of `org`, oldest first.
+    fn files(&self, org: &str) -> Result<Vec<PathBuf>> {
+        let dir = self.dir.join(org);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|path| path.extension().map(|extension| extension == "json").unwrap_or(false))
+            .collect();
+        // the file names are timestamps which sort chronologically
+        files.sort();
+        Ok(files)
+    }
+
+    /// Store `snapshot` and remove the snapshots of its organization exceeding the limit.
+    pub fn save(&self, snapshot: &GHSnapshot) -> Result<PathBuf> {
+        let dir = self.dir.join(&snapshot.org);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", snapshot.fetched_at.format("%Y%m%dT%H%M%S%.3fZ")));
+        fs::write(&path, snapshot.to_json()?)?;
+
+        let files = self.files(&snapshot.org)?;
+        for outdated in &files[..files.len().saturating_sub(self.keep)] {
+            fs::remove_file(outdated)?;
+        }
+        Ok(path)
+    }
+
+    /// The most recent snapshot of `org`.
+    pub fn latest(&self, org: &str) -> Result<Option<GHSnapshot>> {
+        match self.files(org)?.last() {
+            Some(path) => Ok(Some(GHSnapshot::from_json(&fs::read_to_string(path)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// Wait until the rate-limit budget of `client` suffices for `requests` requests.
+async fn wait_for_budget(client: &GHClient, org: &str, requests: usize) {
+    while let Some(limit) = client.rate_limit() {
+        let Some(reset) = limit.reset else { break };
+        if limit.remaining >= requests {
+            break;
+        }
+        log::info!("postponing sync of {org} until {reset}, {} of about {requests} requests left", limit.remaining);
+        async_std::task::sleep((reset - Utc::now()).to_std().unwrap_or_default()).await;
+    }
+}
+
+/// Keep the new or updated `snapshot` in the history of `state` and count its members in the
+/// status, for syncs and webhook deliveries alike.
+pub async fn record(state: &State, snapshot: &GHSnapshot) {
+    if let Some(history) = &state.history {
+        if let Err(msg) = history.save(snapshot) {
+            log::warn!("Failed to keep snapshot of {} in {}: {msg}", snapshot.org, history.dir().display());
+        }
+    }
+    state.status.write().await.entry(snapshot.org.clone()).or_default().members = snapshot.data.len();
+}
+
+/// Crawl `org` once, replacing its snapshot in `state` if successful.
+///
+/// Failed crawls are logged and keep serving the previous snapshot. Members failing in an
+/// otherwise successful crawl keep their previous repositories and fail the sync.
+async fn sync(client: &GHClient, state: &State, org: &str) {
+    // unknown before the first sync
+    let requests = state.snapshots.read().await.get(org).map(|snapshot| recrawl_requests(&snapshot.data));
+    if let Some(requests) = requests {
+        wait_for_budget(client, org, requests).await;
+    }
+
+    log::info!("syncing {org}");
+    let start = Utc::now();
+    let result = client.get_org_member_repositories(org).await;
+    let success = result.as_ref().map(|crawl| crawl.failures.is_empty()).unwrap_or(false);
+    client.metrics().record_sync(org, success, (Utc::now() - start).num_milliseconds() as f64 / 1000.0);
+    match result {
+        Ok(crawl) => {
+            let failures = crawl.failures.clone();
+            let previous = state.snapshots.read().await.get(org).map(|snapshot| snapshot.data.clone()).unwrap_or_default();
+            let snapshot = GHSnapshot::new(org, crawl.into_data_with(&previous));
+            log::info!("synced {} members of {org}", snapshot.data.len());
+            let mut statuses = state.status.write().await;
+            let status = statuses.entry(org.to_string()).or_default();
+            if failures.is_empty() {
+                status.last_success = Some(snapshot.fetched_at);
+            } else {
+                let summary = failure_summary(&failures);
+                log::warn!("{summary}");
+                status.last_failure = Some(snapshot.fetched_at);
+                status.last_error = Some(summary);
+            }
+            status.failures = failures;
+            drop(statuses);
+            record(state, &snapshot).await;
+            state.snapshots.write().await.insert(org.to_string(), snapshot);
+        }
+        Err(msg) => {
+            log::error!("Failed to sync {org}: {msg}");
+            let mut statuses = state.status.write().await;
+            let status = statuses.entry(org.to_string()).or_default();
+            status.last_failure = Some(Utc::now());
+            status.last_error = Some(msg.to_string());
+        }
+    }
+}
+
+/// Crawl `org` right away and then at every upcoming time of `schedule`.
+///
+/// The latest snapshot in the history of `state` is served until the first crawl finished. Runs
+/// missed while a crawl takes longer than the schedule allows are skipped.
+pub async fn sync_on_schedule(client: Arc<GHClient>, state: State, org: String, schedule: Schedule) {
+    if let Some(history) = &state.history {
+        match history.latest(&org) {
+            Ok(Some(snapshot)) => {
+                log::info!("serving {org} as of {} until the first sync", snapshot.fetched_at);
+                state.status.write().await.entry(org.clone()).or_default().members = snapshot.data.len();
+                state.snapshots.write().await.insert(org.clone(), snapshot);
+            }
+            Ok(None) => {}
+            Err(msg) => log::warn!("Failed to read the latest snapshot of {org}: {msg}"),
+        }
+    }
+
+    loop {
+        sync(&client, &state, &org).await;
+        let Some(next) = schedule.upcoming(Utc).next() else {
+            log::warn!("The schedule has no upcoming runs, stopping to sync {org}");
+            break;
+        };
+        state.status.write().await.entry(org.clone()).or_default().next_run = Some(next);
+        async_std::task::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn snapshot(minutes_ago: i64) -> GHSnapshot {
+        let mut snapshot = GHSnapshot::new("octo", Vec::new());
+        snapshot.fetched_at -= Duration::minutes(minutes_ago);
+        snapshot
+    }
+
+    #[test]
+    fn test_history() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history = SnapshotHistory::new(dir.path(), 2);
+        assert!(history.latest("octo")?.is_none());
+
+        for minutes_ago in [30, 10, 20] {
+            history.save(&snapshot(minutes_ago))?;
+        }
+        assert_eq!(2, history.files("octo")?.len());
+        let latest = history.latest("octo")?.unwrap();
+        assert!(Utc::now() - latest.fetched_at < Duration::minutes(11));
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+//! Receiver for GitHub webhook deliveries, keeping the synced snapshots up to date between syncs.
+//!
+//! Configure an organization webhook with content type `application/json`, the secret passed as
+//! `--webhook-secret` and the events *Organizations*, *Repositories* and *Pushes*.
+//!
+//! | event          | actions                           | effect                                          |
+//! |----------------|-----------------------------------|-------------------------------------------------|
+//! | `organization` | `member_added`, `member_removed`  | adds or removes the member                      |
+//! | `repository`   | `created`, `deleted`, `renamed`   | updates the repositories of the owning member   |
+//! | `push`         |                                   | updates language and latest push of the repository |
+//!
+//! Updated snapshots are kept in the `--snapshot-dir` like synced ones, overwriting the snapshot
+//! they update. Members added by a webhook get their repositories with the next sync.
+//!
+//! Deliveries can be tested locally by posting the payloads in `gh-server/fixtures`:
+//!
+//! ```sh
+//! body=gh-server/fixtures/push.json
+//! signature=$(openssl dgst -sha256 -hmac "$GH_SERVER_WEBHOOK_SECRET" < $body | awk '{print $NF}')
+//! curl -H "X-GitHub-Event: push" -H "X-Hub-Signature-256: sha256=$signature" --data-binary @$body localhost:3000/webhook
+//! ```
+use chrono::{DateTime, TimeZone, Utc};
+use gh_client::{GHRepository, GHUser};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tide::{Request, Response, Status, StatusCode};
+
+use crate::api::State;
+use crate::sync;
+
+#[derive(Debug, Deserialize)]
+struct Login {
+    login: String,
+}
+
+/// Fields shared by all deliveries.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    organization: Option<Login>,
+}
+
+/// `push` payloads carry unix timestamps, all other events ISO 8601 dates.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Unix(i64),
+    Iso(DateTime<Utc>),
+}
+
+impl Timestamp {
+    fn to_utc(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Timestamp::Unix(seconds) => Utc.timestamp_opt(*seconds, 0).single(),
+            Timestamp::Iso(date) => Some(*date),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    name: String,
+    language: Option<String>,
+    owner: Login,
+    #[serde(default)]
+    pushed_at: Option<Timestamp>,
+    #[serde(default)]
+    stargazers_count: usize,
+    #[serde(default)]
+    html_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Membership {
+    user: GHUser,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum OrganizationEvent {
+    MemberAdded { membership: Membership },
+    MemberRemoved { membership: Membership },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rename {
+    repository: RenameRepository,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameRepository {
+    name: RenameFrom,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameFrom {
+    from: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum RepositoryEvent {
+    Created { repository: Repository },
+    Deleted { repository: Repository },
+    Renamed { repository: Repository, changes: Rename },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct PushEvent {
+    repository: Repository,
+}
+
+/// Whether `signature`, the value of the `X-Hub-Signature-256` header, is the HMAC of `body`.
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature.strip_prefix("sha256=").and_then(|hex| hex::decode(hex).ok()) else { return false };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn repositories_of<'a>(data: &'a mut [(GHUser, Vec<GHRepository>)], login: &str) -> Option<&'a mut Vec<GHRepository>> {
+    data.iter_mut().find(|(user, _)| user.login == login).map(|(_, repos)| repos)
+}
+
+/// Apply the delivery of a `event` to the data of an organization.
+///
+/// Returns whether the data changed. Unknown events and actions are ignored.
+pub fn apply(data: &mut Vec<(GHUser, Vec<GHRepository>)>, event: &str, payload: &[u8]) -> serde_json::Result<bool> {
+    match event {
+        "organization" => match serde_json::from_slice(payload)? {
+            OrganizationEvent::MemberAdded { membership } => {
+                if data.iter().any(|(user, _)| user.login == membership.user.login) {
+                    return Ok(false);
+                }
+                data.push((membership.user, Vec::new()));
+                Ok(true)
+            }
+            OrganizationEvent::MemberRemoved { membership } => {
+                let before = data.len();
+                data.retain(|(user, _)| user.login != membership.user.login);
+                Ok(data.len() != before)
+            }
+            OrganizationEvent::Other => Ok(false),
+        },
+        "repository" => match serde_json::from_slice(payload)? {
+            RepositoryEvent::Created { repository } => {
+                let Some(repos) = repositories_of(data, &repository.owner.login) else { return Ok(false) };
+                if repos.iter().any(|repo| repo.name == repository.name) {
+                    return Ok(false);
+                }
+                repos.push(GHRepository {
+                    name: repository.name,
+                    language: repository.language,
+                    pushed_at: repository.pushed_at.as_ref().and_then(Timestamp::to_utc),
+                    stargazers_count: repository.stargazers_count,
+                    html_url: repository.html_url,
+                });
+                Ok(true)
+            }
+            RepositoryEvent::Deleted { repository } => {
+                let Some(repos) = repositories_of(data, &repository.owner.login) else { return Ok(false) };
+                let before = repos.len();
+                repos.retain(|repo| repo.name != repository.name);
+                Ok(repos.len() != before)
+            }
+            RepositoryEvent::Renamed { repository, changes } => {
+                let Some(repos) = repositories_of(data, &repository.owner.login) else { return Ok(false) };
+                let Some(repo) = repos.iter_mut().find(|repo| repo.name == changes.repository.name.from) else { return Ok(false) };
+                repo.name = repository.name;
+                Ok(true)
+            }
+            RepositoryEvent::Other => Ok(false),
+        },
+        "push" => {
+            let PushEvent { repository } = serde_json::from_slice(payload)?;
+            let Some(repos) = repositories_of(data, &repository.owner.login) else { return Ok(false) };
+            let pushed_at = repository.pushed_at.as_ref().and_then(Timestamp::to_utc);
+            match repos.iter_mut().find(|repo| repo.name == repository.name) {
+                Some(repo) => {
+                    repo.language = repository.language;
+                    repo.pushed_at = pushed_at;
+                    repo.stargazers_count = repository.stargazers_count;
+                    repo.html_url = repository.html_url;
+                }
+                // created before the member joined or the last sync
+                None => repos.push(GHRepository {
+                    name: repository.name,
+                    language: repository.language,
+                    pushed_at,
+                    stargazers_count: repository.stargazers_count,
+                    html_url: repository.html_url,
+                }),
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// `POST /webhook`, answering 404 if no webhook secret is configured.
+pub async fn receive(mut req: Request<State>) -> tide::Result {
+    let Some(secret) = req.state().webhook_secret.clone() else { return Ok(Response::new(StatusCode::NotFound)) };
+    let signature = req.header("X-Hub-Signature-256").map(|header| header.as_str().to_string());
+    let event = req.header("X-GitHub-Event").map(|header| header.as_str().to_string()).unwrap_or_default();
+    let body = req.body_bytes().await?;
+    if !signature.map(|signature| verify_signature(&secret, &body, &signature)).unwrap_or(false) {
+        return Err(tide::Error::from_str(StatusCode::Unauthorized, "Invalid webhook signature"));
+    }
+
+    let envelope: Envelope = serde_json::from_slice(&body).status(StatusCode::BadRequest)?;
+    let Some(org) = envelope.organization else {
+        log::debug!("ignoring {event} delivery without organization");
+        return Ok(Response::new(StatusCode::NoContent));
+    };
+    // orgs are case-insensitive, the payload spells them as on GitHub
+    let mut snapshots = req.state().snapshots.write().await;
+    let Some(snapshot) = snapshots.values_mut().find(|snapshot| snapshot.org.eq_ignore_ascii_case(&org.login)) else {
+        log::debug!("ignoring {event} delivery of unsynced organization {}", org.login);
+        return Ok(Response::new(StatusCode::NoContent));
+    };
+    if apply(&mut snapshot.data, &event, &body).status(StatusCode::BadRequest)? {
+        log::info!("applied {event} delivery to {}", snapshot.org);
+        sync::record(req.state(), snapshot).await;
+    }
+    Ok(Response::new(StatusCode::NoContent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::app;
+    use crate::sync::SnapshotHistory;
+    use std::sync::Arc;
+    use gh_client::fixtures::{repo, user};
+    use gh_client::snapshot::GHSnapshot;
+    use tide::http::{Method, Url};
+
+    const SECRET: &str = "It's a Secret to Everybody";
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn data() -> Vec<(GHUser, Vec<GHRepository>)> {
+        vec![(user("alice", 1), vec![repo("old-name", Some("Rust"))]), (user("bob", 2), vec![repo("b", Some("Rust"))])]
+    }
+
+    async fn deliver(app: &tide::Server<State>, event: &str, body: &[u8], signature: &str) -> StatusCode {
+        let mut request = tide::http::Request::new(Method::Post, Url::parse("http://localhost/webhook").unwrap());
+        request.insert_header("X-GitHub-Event", event);
+        request.insert_header("X-Hub-Signature-256", signature);
+        request.set_body(body);
+        let response: tide::http::Response = app.respond(request).await.unwrap();
+        response.status()
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        // example from the GitHub documentation on validating webhook deliveries
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_signature(SECRET, b"Hello, World!", signature));
+        assert!(!verify_signature(SECRET, b"Hello, World?", signature));
+        assert!(!verify_signature(SECRET, b"Hello, World!", "sha256=nothex"));
+        assert!(!verify_signature(SECRET, b"Hello, World!", &signature[7..]));
+    }
+
+    #[test]
+    fn test_apply() -> serde_json::Result<()> {
+        let mut data = data();
+        assert!(apply(&mut data, "organization", include_bytes!("../fixtures/member_added.json"))?);
+        assert!(apply(&mut data, "organization", include_bytes!("../fixtures/member_removed.json"))?);
+        assert_eq!(vec!["alice", "carol"], data.iter().map(|(user, _)| user.login.as_str()).collect::<Vec<_>>());
+
+        assert!(apply(&mut data, "repository", include_bytes!("../fixtures/repository_created.json"))?);
+        assert!(apply(&mut data, "repository", include_bytes!("../fixtures/repository_renamed.json"))?);
+        let names = |data: &[(GHUser, Vec<GHRepository>)]| data[0].1.iter().map(|repo| repo.name.clone()).collect::<Vec<_>>();
+        assert_eq!(vec!["better-name", "new-tool"], names(&data));
+
+        assert!(apply(&mut data, "push", include_bytes!("../fixtures/push.json"))?);
+        assert_eq!(Some("Go"), data[0].1[1].language.as_deref());
+        assert_eq!(Some(Utc.with_ymd_and_hms(2022, 12, 20, 13, 0, 0).unwrap()), data[0].1[1].pushed_at);
+
+        assert!(apply(&mut data, "repository", include_bytes!("../fixtures/repository_deleted.json"))?);
+        assert_eq!(vec!["new-tool"], names(&data));
+        assert!(!apply(&mut data, "star", b"{}")?);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_receive() {
+        let state = State { webhook_secret: Some(SECRET.into()), ..Default::default() };
+        state.snapshots.write().await.insert("octo".into(), GHSnapshot::new("octo", data()));
+        let app = app(state.clone());
+
+        let body = include_bytes!("../fixtures/member_removed.json");
+        assert_eq!(StatusCode::Unauthorized, deliver(&app, "organization", body, "sha256=00").await);
+        assert_eq!(2, state.snapshots.read().await["octo"].data.len());
+        assert_eq!(StatusCode::NoContent, deliver(&app, "organization", body, &sign(body)).await);
+        assert_eq!(1, state.snapshots.read().await["octo"].data.len());
+    }
+
+    #[async_std::test]
+    async fn test_receive_into_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = Arc::new(SnapshotHistory::new(dir.path(), 2));
+        let state = State { webhook_secret: Some(SECRET.into()), history: Some(history.clone()), ..Default::default() };
+        state.snapshots.write().await.insert("Octo".into(), GHSnapshot::new("Octo", data()));
+        let app = app(state.clone());
+
+        // the payload spells the organization `octo`
+        let body = include_bytes!("../fixtures/member_removed.json");
+        assert_eq!(StatusCode::NoContent, deliver(&app, "organization", body, &sign(body)).await);
+        assert_eq!(1, state.snapshots.read().await["Octo"].data.len());
+        assert_eq!(1, history.latest("Octo").unwrap().unwrap().data.len());
+        assert_eq!(1, state.status.read().await["Octo"].members);
+    }
+
+    #[async_std::test]
+    async fn test_receive_without_secret() {
+        let app = app(State::default());
+        let body = include_bytes!("../fixtures/member_removed.json");
+        assert_eq!(StatusCode::NotFound, deliver(&app, "organization", body, &sign(body)).await);
+    }
+}